@@ -0,0 +1,39 @@
+//! Compares the interpreter with and without the decoded-instruction cache.
+//! Run with `cargo bench`.
+
+use aoc2019::computer::Computer;
+use aoc2019::day7;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10;
+
+fn bench<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    println!("{:<28} {:>10.3?}", name, elapsed);
+    elapsed
+}
+
+fn main() {
+    let boost = std::fs::read_to_string("input/level9.txt").expect("reading input");
+    let amplifier = std::fs::read_to_string("input/level7.txt").expect("reading input");
+    let phases: Vec<isize> = (5..10).collect();
+
+    for &cache in &[false, true] {
+        let suffix = if cache { "cached" } else { "uncached" };
+        bench(&format!("day9 part2, {}", suffix), || {
+            let mut c = Computer::new_with_input(boost.trim(), 2).unwrap();
+            c.set_decode_cache(cache);
+            c.run_to_halt().unwrap();
+        });
+        bench(&format!("day7 part2, {}", suffix), || {
+            let mut program = Computer::new(amplifier.trim()).unwrap();
+            program.set_decode_cache(cache);
+            day7::max_signal(&program, &phases, true);
+        });
+    }
+}
@@ -0,0 +1,257 @@
+use crate::computer::{Instruction, Parameter};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+#[test]
+fn test_analysis() {
+    use crate::asm::assemble;
+
+    let program = assemble(
+        "
+            IN [x]
+            JZ [x], #skip
+            OUT [x]
+        skip:
+            ADD #1, #2, [patch+1]
+        patch:
+            OUT #0
+            HLT
+            OUT #5
+        x:  db 0
+        ",
+    )
+    .unwrap();
+    let analysis = analyze(&program);
+    let blocks: Vec<_> = analysis
+        .blocks
+        .iter()
+        .map(|b| (b.start, b.end(), b.successors.clone(), b.exit))
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (0, 5, vec![5, 7], Exit::Continue),
+            (5, 7, vec![7], Exit::Continue),
+            (7, 14, vec![], Exit::Halt)
+        ]
+    );
+    assert_eq!(analysis.unreachable, [Range { start: 14, end: 17 }]);
+    assert_eq!(analysis.self_modifying, [(7, 12)]);
+    assert_eq!(analysis.inputs, [0]);
+    assert_eq!(analysis.outputs, [5, 11]);
+
+    let dot = analysis.to_dot();
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("    b0 [label=\"0: IN   [16]\\l2: JZ   [16], #7\\l\"];\n"));
+    assert!(dot.contains("    b0 -> b7;\n"));
+
+    // jumps to a computed address end the known part of the graph
+    let analysis = analyze(&[3, 5, 105, 1, 5, 99]);
+    assert_eq!(analysis.blocks[0].exit, Exit::Indirect);
+    assert_eq!(analysis.unreachable, [Range { start: 5, end: 6 }]);
+}
+
+/// How a basic block ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Execution goes on with the successors.
+    Continue,
+    Halt,
+    /// A jump to an address that is only known at runtime.
+    Indirect,
+    /// Execution runs into something that does not decode, or off the end
+    /// of memory.
+    Invalid,
+}
+
+/// A straight run of instructions, which is only ever entered at the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    /// The start addresses of the blocks execution can go on with.
+    pub successors: Vec<usize>,
+    pub exit: Exit,
+}
+
+impl Block {
+    /// The address right after the last instruction.
+    pub fn end(&self) -> usize {
+        match self.instructions.last() {
+            Some((addr, inst)) => addr + inst.width(),
+            None => self.start,
+        }
+    }
+}
+
+/// What can be found out about a program without running it, starting from
+/// address 0 and following all the jumps with immediate targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub blocks: Vec<Block>,
+    /// The memory ranges that are not part of any reachable instruction.
+    pub unreachable: Vec<Range<usize>>,
+    /// Instructions at a pc that write to an address inside reachable code.
+    /// Only position mode writes are known statically.
+    pub self_modifying: Vec<(usize, usize)>,
+    /// The addresses of all reachable input instructions.
+    pub inputs: Vec<usize>,
+    /// The addresses of all reachable output instructions.
+    pub outputs: Vec<usize>,
+}
+
+/// Where execution can go after `inst`, and whether that is all of it.
+fn successors(addr: usize, inst: &Instruction) -> (Vec<usize>, Exit) {
+    let next = addr + inst.width();
+    match inst {
+        Instruction::Halt => (vec![], Exit::Halt),
+        Instruction::Jump(op, cond, target) => {
+            let taken = match cond {
+                Parameter::Immediate(v) => Some(op.taken(v)),
+                _ => None,
+            };
+            let target = match target {
+                Parameter::Immediate(target) if *target >= 0 => Some(*target as usize),
+                _ => None,
+            };
+            match (taken, target) {
+                (Some(false), _) => (vec![next], Exit::Continue),
+                (Some(true), Some(target)) => (vec![target], Exit::Continue),
+                (Some(true), None) => (vec![], Exit::Indirect),
+                (None, Some(target)) => (vec![next, target], Exit::Continue),
+                (None, None) => (vec![next], Exit::Indirect),
+            }
+        }
+        _ => (vec![next], Exit::Continue),
+    }
+}
+
+/// The parameter an instruction writes to, if any.
+fn destination(inst: &Instruction) -> Option<&Parameter> {
+    match inst {
+        Instruction::Arithmetic(_, _, _, dst) | Instruction::Relation(_, _, _, dst) => Some(dst),
+        Instruction::Input(dst) => Some(dst),
+        _ => None,
+    }
+}
+
+pub fn analyze(memory: &[isize]) -> Analysis {
+    analyze_from(memory, &[0])
+}
+
+/// Like `analyze`, but starts from all of `entries`, which is useful when
+/// some jump targets are known to be computed at runtime.
+pub fn analyze_from(memory: &[isize], entries: &[usize]) -> Analysis {
+    // find every reachable instruction
+    let mut reachable = BTreeMap::new();
+    let mut leaders: BTreeSet<usize> = entries.iter().copied().collect();
+    let mut todo = entries.to_vec();
+    while let Some(addr) = todo.pop() {
+        if addr >= memory.len() || reachable.contains_key(&addr) {
+            continue;
+        }
+        let inst = match Instruction::decode(memory, addr) {
+            Ok(inst) => inst,
+            Err(_) => continue,
+        };
+        let (next, _) = successors(addr, &inst);
+        if let Instruction::Jump(..) = inst {
+            leaders.extend(next.iter().copied());
+        }
+        todo.extend(next);
+        reachable.insert(addr, inst);
+    }
+
+    // split them into blocks, which end at jumps and before jump targets
+    let mut blocks = vec![];
+    for &start in leaders.iter().filter(|addr| reachable.contains_key(addr)) {
+        let mut block = Block {
+            start,
+            instructions: vec![],
+            successors: vec![],
+            exit: Exit::Continue,
+        };
+        let mut addr = start;
+        loop {
+            let inst = reachable[&addr].clone();
+            let (next, exit) = successors(addr, &inst);
+            let is_jump = matches!(inst, Instruction::Jump(..));
+            block.instructions.push((addr, inst));
+            if exit != Exit::Continue || is_jump || leaders.contains(&next[0]) {
+                block.successors = next;
+                block.exit = exit;
+                break;
+            }
+            if !reachable.contains_key(&next[0]) {
+                block.exit = Exit::Invalid;
+                break;
+            }
+            addr = next[0];
+        }
+        if block.successors.iter().any(|s| !reachable.contains_key(s)) {
+            block.successors.retain(|s| reachable.contains_key(s));
+            block.exit = Exit::Invalid;
+        }
+        blocks.push(block);
+    }
+
+    let code: BTreeSet<usize> = reachable
+        .iter()
+        .flat_map(|(addr, inst)| *addr..addr + inst.width())
+        .collect();
+    let mut unreachable: Vec<Range<usize>> = vec![];
+    for addr in (0..memory.len()).filter(|addr| !code.contains(addr)) {
+        match unreachable.last_mut() {
+            Some(range) if range.end == addr => range.end += 1,
+            _ => unreachable.push(addr..addr + 1),
+        }
+    }
+
+    let mut analysis = Analysis {
+        blocks,
+        unreachable,
+        self_modifying: vec![],
+        inputs: vec![],
+        outputs: vec![],
+    };
+    for (&addr, inst) in &reachable {
+        if let Some(Parameter::Position(dst)) = destination(inst) {
+            if code.contains(dst) {
+                analysis.self_modifying.push((addr, *dst));
+            }
+        }
+        match inst {
+            Instruction::Input(_) => analysis.inputs.push(addr),
+            Instruction::Output(_) => analysis.outputs.push(addr),
+            _ => {}
+        }
+    }
+    analysis
+}
+
+impl Analysis {
+    /// The control-flow graph in the Graphviz DOT format. Halting blocks
+    /// are drawn with a double border, and blocks that end in an indirect
+    /// jump or invalid code with a dashed one.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for block in &self.blocks {
+            let mut label = String::new();
+            for (addr, inst) in &block.instructions {
+                write!(label, "{}: {}\\l", addr, inst).unwrap();
+            }
+            let style = match block.exit {
+                Exit::Continue => "",
+                Exit::Halt => ", peripheries=2",
+                Exit::Indirect | Exit::Invalid => ", style=dashed",
+            };
+            writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+            for successor in &block.successors {
+                writeln!(dot, "    b{} -> b{};", block.start, successor).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
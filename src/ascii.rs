@@ -0,0 +1,156 @@
+use crate::computer::{Computer, Error, Step};
+use std::io::{self, BufRead, Write};
+
+#[test]
+fn test_ascii() {
+    // echoes one line of input, then outputs 1000
+    let program = "3,100,4,100,1008,100,10,101,1006,101,0,104,1000,99";
+    let mut ascii = Ascii::new(Computer::new(program).unwrap());
+    assert_eq!(ascii.run().unwrap(), Step::Waiting);
+    assert!(ascii.take_output().is_empty());
+    ascii.push_line("hi");
+    assert_eq!(ascii.run().unwrap(), Step::Halted);
+    assert_eq!(
+        ascii.take_output(),
+        [Output::Line("hi".into()), Output::Value(1000)]
+    );
+
+    let mut ascii = Ascii::new(Computer::new("104,65,99").unwrap());
+    ascii.run().unwrap();
+    assert_eq!(ascii.take_output(), [Output::Line("A".into())]);
+
+    // a value in the middle of a line keeps its place
+    let mut ascii = Ascii::new(Computer::new("104,65,104,1000,104,66,104,10,99").unwrap());
+    ascii.run().unwrap();
+    assert_eq!(
+        ascii.take_output(),
+        [
+            Output::Line("A".into()),
+            Output::Value(1000),
+            Output::Line("B".into())
+        ]
+    );
+
+    let mut ascii = Ascii::new(Computer::new(program).unwrap());
+    let mut out = vec![];
+    let step = ascii.interactive("hey\n".as_bytes(), &mut out).unwrap();
+    assert_eq!(step, Step::Halted);
+    assert_eq!(out, b"hey\n1000\n");
+}
+
+/// Output of a program that talks ASCII.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// A line of text, without the newline. A value in the middle of a
+    /// line splits it in two.
+    Line(String),
+    /// A value outside of the ASCII range, which is usually the answer.
+    Value(isize),
+}
+
+fn is_ascii(value: isize) -> bool {
+    (0..=127).contains(&value)
+}
+
+/// Runs a `Computer` with strings as input and lines as output.
+pub struct Ascii {
+    computer: Computer,
+    line: String,
+    output: Vec<Output>,
+}
+
+impl Ascii {
+    pub fn new(computer: Computer) -> Ascii {
+        Ascii {
+            computer,
+            line: String::new(),
+            output: vec![],
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn into_computer(self) -> Computer {
+        self.computer
+    }
+
+    /// Queues every char of `line` as input, followed by a newline.
+    pub fn push_line(&mut self, line: &str) {
+        for c in line.chars().chain(Some('\n')) {
+            self.computer.push_input(c as isize);
+        }
+    }
+
+    /// Runs until the program halts or waits for input. Once it halts, an
+    /// unfinished last line counts as a line as well.
+    pub fn run(&mut self) -> Result<Step, Error> {
+        let step = self.computer.resume()?;
+        while let Some(value) = self.computer.pop_output() {
+            match value {
+                10 => self
+                    .output
+                    .push(Output::Line(std::mem::take(&mut self.line))),
+                _ if is_ascii(value) => self.line.push(value as u8 as char),
+                _ => {
+                    if !self.line.is_empty() {
+                        self.output
+                            .push(Output::Line(std::mem::take(&mut self.line)));
+                    }
+                    self.output.push(Output::Value(value));
+                }
+            }
+        }
+        if step == Step::Halted && !self.line.is_empty() {
+            self.output
+                .push(Output::Line(std::mem::take(&mut self.line)));
+        }
+        Ok(step)
+    }
+
+    /// The lines and values collected by `run` so far.
+    pub fn take_output(&mut self) -> Vec<Output> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Connects the program to a terminal: text goes straight to `output`,
+    /// other values on a line of their own, and whenever the program waits
+    /// for input a line is read from `input`. This returns once the program
+    /// halts, or when it waits and `input` is at its end.
+    pub fn interactive<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> io::Result<Step> {
+        let mut at_line_start = true;
+        loop {
+            let step = self.computer.resume().map_err(io::Error::other)?;
+            while let Some(value) = self.computer.pop_output() {
+                if is_ascii(value) {
+                    write!(output, "{}", value as u8 as char)?;
+                    at_line_start = value == 10;
+                } else {
+                    if !at_line_start {
+                        writeln!(output)?;
+                    }
+                    writeln!(output, "{}", value)?;
+                    at_line_start = true;
+                }
+            }
+            output.flush()?;
+            if step != Step::Waiting {
+                return Ok(step);
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(step);
+            }
+            self.push_line(line.trim_end_matches(&['\r', '\n'][..]));
+        }
+    }
+}
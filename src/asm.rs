@@ -0,0 +1,353 @@
+use crate::computer::{ArithmeticOp, Instruction, JumpOp, Parameter, RelationOp};
+use std::collections::HashMap;
+use std::fmt;
+
+#[test]
+fn test_asm() {
+    use crate::computer::{format_memory, parse_memory, Computer};
+    use crate::disasm;
+
+    // prints the numbers from 1 to 3
+    let program = assemble(
+        "
+            ADD #0, #1, [counter]   ; counter = 1
+        loop:
+            OUT [counter]
+            ADD [counter], #1, [counter]
+            LT [counter], #4, [cond]
+            JNZ [cond], #loop
+            HLT
+        counter: db 0
+        cond:    db 0
+        ",
+    )
+    .unwrap();
+    assert_eq!(
+        format_memory(&program),
+        "1101,0,1,18,4,18,1001,18,1,18,1007,18,4,19,1005,19,4,99,0,0"
+    );
+    let mut c = Computer::new(&format_memory(&program)).unwrap();
+    c.run().unwrap();
+    assert_eq!(c.output(), &[1, 2, 3]);
+
+    // relative operands, label arithmetic and numeric address checks
+    assert_eq!(
+        assemble("ARB #end\n2: IN rb-1\nOUT [end-1]\nend: HLT").unwrap(),
+        vec![109, 6, 203, -1, 4, 5, 99]
+    );
+
+    // disassembling and assembling again gives back the original program
+    let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+    let complex = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+    for program in &[quine, complex] {
+        let mem = parse_memory(program).unwrap();
+        assert_eq!(assemble(&disasm::listing(&mem)).unwrap(), mem);
+    }
+
+    let err = |source: &str| assemble(source).unwrap_err();
+    assert_eq!(
+        err("HLT\nFOO #1").kind,
+        ErrorKind::UnknownMnemonic("FOO".into())
+    );
+    assert_eq!(err("HLT\nFOO #1").line, 2);
+    assert_eq!(
+        err("OUT [nowhere]").kind,
+        ErrorKind::UndefinedLabel("nowhere".into())
+    );
+    assert_eq!(
+        err("a: HLT\na: HLT").kind,
+        ErrorKind::DuplicateLabel("a".into())
+    );
+    assert_eq!(
+        err("ADD #1, #2").kind,
+        ErrorKind::OperandCount {
+            expected: 3,
+            found: 2
+        }
+    );
+    assert_eq!(err("OUT [-1]").kind, ErrorKind::NegativeAddress(-1));
+    assert_eq!(
+        err("HLT\n4: HLT").kind,
+        ErrorKind::AddressMismatch {
+            expected: 4,
+            actual: 1
+        }
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownMnemonic(String),
+    InvalidOperand(String),
+    OperandCount {
+        expected: usize,
+        found: usize,
+    },
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    NegativeAddress(isize),
+    /// A numeric label like `12:` did not match the actual address.
+    AddressMismatch {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// An assembler error, with the 1-based source `line` it happened on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownMnemonic(m) => write!(f, "unknown mnemonic `{}`", m),
+            ErrorKind::InvalidOperand(o) => write!(f, "invalid operand `{}`", o),
+            ErrorKind::OperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            ErrorKind::UndefinedLabel(l) => write!(f, "undefined label `{}`", l),
+            ErrorKind::DuplicateLabel(l) => write!(f, "duplicate label `{}`", l),
+            ErrorKind::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            ErrorKind::AddressMismatch { expected, actual } => {
+                write!(f, "expected address {}, but is at {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A number, a label, or a label with a constant offset like `end-1`.
+struct Expr<'a> {
+    label: Option<&'a str>,
+    offset: isize,
+}
+
+enum Operand<'a> {
+    Position(Expr<'a>),
+    Immediate(Expr<'a>),
+    Relative(isize),
+}
+
+enum Statement<'a> {
+    Instruction(&'a str, Vec<Operand<'a>>),
+    Data(Vec<Expr<'a>>),
+}
+
+/// All the mnemonics, with their number of operands.
+const MNEMONICS: [(&str, usize); 10] = [
+    ("ADD", 3),
+    ("MUL", 3),
+    ("IN", 1),
+    ("OUT", 1),
+    ("JNZ", 2),
+    ("JZ", 2),
+    ("LT", 3),
+    ("EQ", 3),
+    ("ARB", 1),
+    ("HLT", 0),
+];
+
+fn build_instruction(mnemonic: &str, params: Vec<Parameter>) -> Instruction {
+    let mut params = params.into_iter();
+    let mut next = || params.next().expect("operand count was checked");
+    match mnemonic {
+        "ADD" => Instruction::Arithmetic(ArithmeticOp::Add, next(), next(), next()),
+        "MUL" => Instruction::Arithmetic(ArithmeticOp::Multiply, next(), next(), next()),
+        "IN" => Instruction::Input(next()),
+        "OUT" => Instruction::Output(next()),
+        "JNZ" => Instruction::Jump(JumpOp::IfNotZero, next(), next()),
+        "JZ" => Instruction::Jump(JumpOp::IfZero, next(), next()),
+        "LT" => Instruction::Relation(RelationOp::LessThan, next(), next(), next()),
+        "EQ" => Instruction::Relation(RelationOp::Equal, next(), next(), next()),
+        "ARB" => Instruction::AdjustBase(next()),
+        _ => Instruction::Halt,
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    s != "rb" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_expr(s: &str) -> Result<Expr<'_>, ErrorKind> {
+    let s = s.trim();
+    let invalid = || ErrorKind::InvalidOperand(s.to_string());
+    if let Ok(offset) = s.parse() {
+        return Ok(Expr {
+            label: None,
+            offset,
+        });
+    }
+    // `label`, `label+N` or `label-N`
+    let (label, offset) = match s.find(['+', '-']) {
+        Some(idx) => (
+            s[..idx].trim(),
+            s[idx..].replace(' ', "").parse().map_err(|_| invalid())?,
+        ),
+        None => (s, 0),
+    };
+    if !is_label(label) {
+        return Err(invalid());
+    }
+    Ok(Expr {
+        label: Some(label),
+        offset,
+    })
+}
+
+fn parse_operand(s: &str) -> Result<Operand<'_>, ErrorKind> {
+    let s = s.trim();
+    let invalid = || ErrorKind::InvalidOperand(s.to_string());
+    if let Some(imm) = s.strip_prefix('#') {
+        Ok(Operand::Immediate(parse_expr(imm)?))
+    } else if let Some(addr) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        Ok(Operand::Position(parse_expr(addr)?))
+    } else if let Some(offset) = s.strip_prefix("rb") {
+        let offset = offset.replace(' ', "");
+        let offset = offset.strip_prefix('+').unwrap_or(&offset);
+        offset.parse().map(Operand::Relative).map_err(|_| invalid())
+    } else {
+        Err(invalid())
+    }
+}
+
+fn parse_statement(line: &str) -> Result<Option<Statement<'_>>, ErrorKind> {
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let (mnemonic, rest) = match line.find(char::is_whitespace) {
+        Some(idx) => (&line[..idx], line[idx..].trim()),
+        None => (line, ""),
+    };
+    let operands: Vec<&str> = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',').collect()
+    };
+    if mnemonic.eq_ignore_ascii_case("db") {
+        let values = operands
+            .into_iter()
+            .map(parse_expr)
+            .collect::<Result<_, _>>()?;
+        return Ok(Some(Statement::Data(values)));
+    }
+    let upper = mnemonic.to_ascii_uppercase();
+    let (mnemonic, expected) = *MNEMONICS
+        .iter()
+        .find(|(m, _)| *m == upper)
+        .ok_or_else(|| ErrorKind::UnknownMnemonic(mnemonic.to_string()))?;
+    if operands.len() != expected {
+        return Err(ErrorKind::OperandCount {
+            expected,
+            found: operands.len(),
+        });
+    }
+    let operands = operands
+        .into_iter()
+        .map(parse_operand)
+        .collect::<Result<_, _>>()?;
+    Ok(Some(Statement::Instruction(mnemonic, operands)))
+}
+
+/// Assembles `source` into memory words.
+///
+/// Every line holds an optional `label:`, followed by either an instruction
+/// like `ADD [a], #1, rb-2` or a `db 1, 2, label` data directive. Comments
+/// start with `;`. A numeric label like `12:` does not define anything, but
+/// checks that the next statement is placed at that address, which makes
+/// `disasm::listing` output valid assembler input.
+pub fn assemble(source: &str) -> Result<Vec<isize>, Error> {
+    // first pass: parse everything and figure out where the labels are
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut addr = 0;
+    for (line_no, line) in source.lines().enumerate() {
+        let line_no = line_no + 1;
+        let error = |kind| Error {
+            line: line_no,
+            kind,
+        };
+        let mut line = line.split(';').next().unwrap_or("").trim();
+        while let Some(idx) = line.find(':') {
+            let label = line[..idx].trim();
+            if let Ok(expected) = label.parse::<usize>() {
+                if expected != addr {
+                    return Err(error(ErrorKind::AddressMismatch {
+                        expected,
+                        actual: addr,
+                    }));
+                }
+            } else if !is_label(label) {
+                return Err(error(ErrorKind::InvalidOperand(label.to_string())));
+            } else if labels.insert(label, addr).is_some() {
+                return Err(error(ErrorKind::DuplicateLabel(label.to_string())));
+            }
+            line = line[idx + 1..].trim();
+        }
+        if let Some(statement) = parse_statement(line).map_err(error)? {
+            addr += match &statement {
+                Statement::Instruction(_, operands) => 1 + operands.len(),
+                Statement::Data(values) => values.len(),
+            };
+            statements.push((line_no, statement));
+        }
+    }
+
+    // second pass: resolve the labels and encode everything
+    let mut memory = vec![];
+    for (line_no, statement) in statements {
+        let error = |kind| Error {
+            line: line_no,
+            kind,
+        };
+        let resolve = |expr: &Expr| -> Result<isize, Error> {
+            let base = match expr.label {
+                Some(label) => *labels
+                    .get(label)
+                    .ok_or_else(|| error(ErrorKind::UndefinedLabel(label.to_string())))?
+                    as isize,
+                None => 0,
+            };
+            Ok(base + expr.offset)
+        };
+        match statement {
+            Statement::Data(values) => {
+                for value in &values {
+                    memory.push(resolve(value)?);
+                }
+            }
+            Statement::Instruction(mnemonic, operands) => {
+                let mut params = vec![];
+                for operand in &operands {
+                    params.push(match operand {
+                        Operand::Immediate(expr) => Parameter::Immediate(resolve(expr)?),
+                        Operand::Relative(offset) => Parameter::Relative(*offset),
+                        Operand::Position(expr) => {
+                            let addr = resolve(expr)?;
+                            if addr < 0 {
+                                return Err(error(ErrorKind::NegativeAddress(addr)));
+                            }
+                            Parameter::Position(addr as usize)
+                        }
+                    });
+                }
+                memory.extend(build_instruction(mnemonic, params).encode());
+            }
+        }
+    }
+    Ok(memory)
+}
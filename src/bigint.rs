@@ -0,0 +1,249 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+#[test]
+fn test_bigint() {
+    let big = |s: &str| s.parse::<BigInt>().unwrap();
+
+    for s in &[
+        "0",
+        "1",
+        "-1",
+        "4294967296",
+        "-123456789012345678901234567890",
+    ] {
+        assert_eq!(big(s).to_string(), *s);
+    }
+    assert_eq!(big("-0"), BigInt::default());
+    assert!("12a".parse::<BigInt>().is_err());
+    assert!("".parse::<BigInt>().is_err());
+
+    assert_eq!(
+        big("1125899906842624").mul(&big("1125899906842624")),
+        big("1267650600228229401496703205376")
+    );
+    assert_eq!(big("-5").add(&big("3")), big("-2"));
+    assert_eq!(big("5").add(&big("-5")), BigInt::default());
+    assert_eq!(
+        big("18446744073709551616").add(&big("-1")),
+        big("18446744073709551615")
+    );
+    assert_eq!(big("-7").mul(&big("6")), big("-42"));
+
+    assert!(big("-10") < big("-9"));
+    assert!(big("-1") < big("0"));
+    assert!(big("4294967296") > big("4294967295"));
+
+    assert_eq!(BigInt::from(isize::MIN).to_isize(), Some(isize::MIN));
+    assert_eq!(big("9223372036854775808").to_isize(), None);
+}
+
+/// An arbitrary-precision signed integer.
+///
+/// This only supports what Intcode needs: parsing, printing, comparing,
+/// adding and multiplying.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    /// Little-endian base 2^32 digits, without trailing zeros. Zero is an
+    /// empty `Vec`, and never negative.
+    magnitude: Vec<u32>,
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum =
+            carry + a.get(i).copied().unwrap_or(0) as u64 + b.get(i).copied().unwrap_or(0) as u64;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Computes `a - b`, where `a` must be the larger one.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut diff = *digit as i64 - borrow - b.get(i).copied().unwrap_or(0) as i64;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        result.push(diff as u32);
+    }
+    result
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::new(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        let mut result = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.magnitude.iter().enumerate() {
+                let product = *a as u64 * *b as u64 + result[i + j] as u64 + carry;
+                result[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            result[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, result)
+    }
+
+    /// Divides the magnitude in place by a small divisor, returning the remainder.
+    fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for digit in magnitude.iter_mut().rev() {
+            let current = (rem << 32) | *digit as u64;
+            *digit = (current / divisor as u64) as u32;
+            rem = current % divisor as u64;
+        }
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        rem as u32
+    }
+
+    pub fn to_isize(&self) -> Option<isize> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0i128, |acc, digit| (acc << 32) | *digit as i128);
+        let value = if self.negative { -magnitude } else { magnitude };
+        if value < isize::MIN as i128 || value > isize::MAX as i128 {
+            return None;
+        }
+        Some(value as isize)
+    }
+}
+
+impl From<isize> for BigInt {
+    fn from(value: isize) -> BigInt {
+        let mut magnitude = value.unsigned_abs() as u64;
+        let mut digits = vec![];
+        while magnitude > 0 {
+            digits.push(magnitude as u32);
+            magnitude >>= 32;
+        }
+        BigInt::new(value < 0, digits)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid digit found in string")
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<BigInt, ParseBigIntError> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+        let ten = BigInt::from(10);
+        let mut result = BigInt::default();
+        for b in digits.bytes() {
+            result = result.mul(&ten).add(&BigInt::from((b - b'0') as isize));
+        }
+        Ok(BigInt::new(negative, result.magnitude))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.magnitude.is_empty() {
+            return write!(f, "0");
+        }
+        // split into chunks of 9 decimal digits, least significant first
+        let mut magnitude = self.magnitude.clone();
+        let mut chunks = vec![];
+        while !magnitude.is_empty() {
+            chunks.push(BigInt::div_rem_small(&mut magnitude, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use crate::bigint::BigInt;
+use std::fmt;
+use std::str::FromStr;
+
+#[test]
+fn test_cells() {
+    use crate::computer::{Computer, ErrorKind};
+
+    // squares its input twice
+    let program = "3,0,2,0,0,0,2,0,0,0,4,0,99";
+    fn square_twice<V: Cell>(program: &str, input: V) -> Result<V, ErrorKind<V>> {
+        let mut c = Computer::<V>::parse(program).unwrap();
+        c.push_input(input);
+        c.run_to_halt().map_err(|err| err.kind)
+    }
+
+    assert_eq!(square_twice::<isize>(program, 1000), Ok(1_000_000_000_000));
+    assert_eq!(
+        square_twice::<i64>(program, 100_000),
+        Err(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        square_twice::<i128>(program, 100_000),
+        Ok(100_000_000_000_000_000_000)
+    );
+    assert_eq!(
+        square_twice::<BigInt>(program, "100000000000".parse().unwrap()),
+        Ok("100000000000000000000000000000000000000000000"
+            .parse()
+            .unwrap())
+    );
+}
+
+/// The type of a single memory cell of a `Computer`.
+///
+/// All the arithmetic is checked, and an overflow is reported as
+/// `ErrorKind::Overflow` instead of wrapping around or panicking.
+pub trait Cell: Clone + Default + Ord + fmt::Debug + fmt::Display + FromStr {
+    fn from_isize(value: isize) -> Self;
+    /// Converts to an `isize`, if the value fits.
+    fn to_isize(&self) -> Option<isize>;
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn is_negative(&self) -> bool {
+        *self < Self::default()
+    }
+}
+
+macro_rules! primitive_cell {
+    ($t:ty) => {
+        impl Cell for $t {
+            fn from_isize(value: isize) -> Self {
+                value as $t
+            }
+            fn to_isize(&self) -> Option<isize> {
+                use std::convert::TryFrom;
+                isize::try_from(*self).ok()
+            }
+            fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *rhs)
+            }
+            fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *rhs)
+            }
+        }
+    };
+}
+
+primitive_cell!(isize);
+primitive_cell!(i64);
+primitive_cell!(i128);
+
+impl Cell for BigInt {
+    fn from_isize(value: isize) -> Self {
+        BigInt::from(value)
+    }
+    fn to_isize(&self) -> Option<isize> {
+        BigInt::to_isize(self)
+    }
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self.add(rhs))
+    }
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self.mul(rhs))
+    }
+}
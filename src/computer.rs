@@ -1,99 +1,471 @@
+use crate::cell::Cell;
+use crate::io::{Io, Queues};
+use crate::isa::{Access, Action, InstructionSet};
+use crate::memory::Memory;
 use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
 
-enum Parameter {
+#[test]
+fn test_errors() {
+    fn run_test(mem: &str) -> Error {
+        let mut c = Computer::new(mem).unwrap();
+        c.run().err().expect("expected an error")
+    }
+
+    assert_eq!(
+        Computer::new("1,2,x,4").err().unwrap(),
+        Error {
+            pc: 2,
+            word: 0,
+            kind: ErrorKind::InvalidNumber("x".into())
+        }
+    );
+
+    let err = run_test("1101,1,1,5,42,0");
+    assert_eq!((err.pc, err.word), (4, 42));
+    assert_eq!(err.kind, ErrorKind::InvalidOpcode(42));
+
+    assert_eq!(
+        run_test("301,0,0,0,99").kind,
+        ErrorKind::InvalidParameterMode(3)
+    );
+    assert_eq!(run_test("11101,1,1,5,99").kind, ErrorKind::WriteToImmediate);
+    assert_eq!(
+        run_test("109,-5,204,1,99").kind,
+        ErrorKind::NegativeAddress(-4)
+    );
+    assert_eq!(run_test("4,-1,99").kind, ErrorKind::NegativeAddress(-1));
+    assert_eq!(
+        run_test("1105,1,-1,99").kind,
+        ErrorKind::NegativeAddress(-1)
+    );
+    let mut c = Computer::new("1106,1,-1,104,5,99").unwrap();
+    assert_eq!(c.run_to_halt(), Ok(5));
+    assert_eq!(
+        run_test("1102,9223372036854775807,2,0,99").kind,
+        ErrorKind::Overflow
+    );
+    assert_eq!(run_test("99").kind, ErrorKind::HaltWithoutOutput);
+    assert_eq!(
+        Computer::new("3,0,99")
+            .unwrap()
+            .run_to_halt()
+            .err()
+            .unwrap()
+            .kind,
+        ErrorKind::MissingInput
+    );
+}
+
+#[test]
+fn test_budget() {
+    // loops forever, outputting 1 on every iteration
+    let mut c = Computer::new("104,1,1105,1,0").unwrap();
+    c.set_budget(Some(5));
+    assert!(matches!(c.run(), Ok(RunResult::OutOfBudget)));
+    assert_eq!(c.output(), &[1, 1, 1]);
+    assert_eq!(c.pc(), 2);
+    assert_eq!(c.run_to_halt().unwrap_err().kind, ErrorKind::OutOfBudget);
+
+    c.set_budget(Some(2));
+    assert!(matches!(c.run(), Ok(RunResult::OutOfBudget)));
+    assert_eq!(c.pc(), 2);
+    assert_eq!(c.cycles().total(), 7);
+    assert_eq!(c.cycles().iter().collect::<Vec<_>>(), [(4, 4), (5, 3)]);
+
+    // halting is free, also when running a halted program again
+    let mut c = Computer::new("104,1,99").unwrap();
+    c.set_budget(Some(1));
+    let mut profiler = crate::profile::Profiler::new();
+    for _ in 0..3 {
+        assert!(matches!(
+            c.run_observed(&mut profiler),
+            Ok(RunResult::Completed(1))
+        ));
+    }
+    assert_eq!(c.cycles().total(), 1);
+    assert_eq!(profiler.total(), 1);
+    assert_eq!(c.budget(), Some(0));
+}
+
+#[test]
+fn test_decode_cache() {
+    // outputs 7 for long enough to get cached, then changes its own output
+    // instruction to output 8
+    let program = "104,7,1001,20,1,20,1007,20,999,21,1005,21,0,1101,0,8,1,1105,1,0,0,0";
+    for &cache in &[true, false] {
+        let mut c = Computer::new(program).unwrap();
+        c.set_decode_cache(cache);
+        c.set_budget(Some(4 * 999 + 3));
+        c.run().unwrap();
+        assert_eq!(c.output().len(), 1000);
+        assert_eq!(c.output().back(), Some(&8));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind<V = isize> {
+    /// The program text contained something that is not a number.
+    InvalidNumber(String),
+    /// The opcode, or the whole instruction word if it is out of range.
+    InvalidOpcode(V),
+    InvalidParameterMode(isize),
+    WriteToImmediate,
+    NegativeAddress(V),
+    /// An address that does not fit into a `usize`.
+    AddressOutOfRange(V),
+    /// An arithmetic result that does not fit into the cell type.
+    Overflow,
+    HaltWithoutOutput,
+    /// `run_to_halt` was used, but the program is waiting for more input.
+    MissingInput,
+    /// Writing to the address would allocate more than the memory limit.
+    MemoryLimit(usize),
+    /// `run_to_halt` was used, but the instruction budget ran out.
+    OutOfBudget,
+    /// An extension instruction failed, for the given reason.
+    Extension(&'static str),
+}
+
+/// Everything that can go wrong while loading or running a program.
+///
+/// `pc` is the address of the failing instruction and `word` its raw value.
+/// For `InvalidNumber`, `pc` is the address the number would have been
+/// loaded to, and `word` is `0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error<V = isize> {
+    pub pc: usize,
+    pub word: V,
+    pub kind: ErrorKind<V>,
+}
+
+impl<V: fmt::Display> fmt::Display for ErrorKind<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::InvalidNumber(s) => write!(f, "invalid number `{}`", s),
+            ErrorKind::InvalidOpcode(code) => write!(f, "invalid opcode {}", code),
+            ErrorKind::InvalidParameterMode(mode) => {
+                write!(f, "unknown parameter mode {}", mode)
+            }
+            ErrorKind::WriteToImmediate => write!(f, "writing to an Immediate parameter"),
+            ErrorKind::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            ErrorKind::AddressOutOfRange(addr) => write!(f, "address {} out of range", addr),
+            ErrorKind::Overflow => write!(f, "arithmetic overflow"),
+            ErrorKind::HaltWithoutOutput => write!(f, "halting without output"),
+            ErrorKind::MissingInput => write!(f, "waiting for input"),
+            ErrorKind::MemoryLimit(addr) => {
+                write!(f, "writing to address {} exceeds the memory limit", addr)
+            }
+            ErrorKind::OutOfBudget => write!(f, "instruction budget exhausted"),
+            ErrorKind::Extension(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl<V: fmt::Display> fmt::Display for Error<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::InvalidNumber(_) => write!(f, "{} at address {}", self.kind, self.pc),
+            _ => write!(
+                f,
+                "{} at pc {} (instruction {})",
+                self.kind, self.pc, self.word
+            ),
+        }
+    }
+}
+
+impl<V: fmt::Debug + fmt::Display> std::error::Error for Error<V> {}
+
+/// Turns a cell value into a memory address.
+fn to_address<V: Cell>(value: &V) -> Result<usize, ErrorKind<V>> {
+    if value.is_negative() {
+        return Err(ErrorKind::NegativeAddress(value.clone()));
+    }
+    match value.to_isize() {
+        Some(addr) => Ok(addr as usize),
+        None => Err(ErrorKind::AddressOutOfRange(value.clone())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Parameter<V = isize> {
     Position(usize),
-    Immediate(isize),
-    Relative(isize),
+    Immediate(V),
+    Relative(V),
 }
 
-impl Parameter {
-    fn read(&self, computer: &Computer) -> isize {
-        let addr = match self {
-            Parameter::Immediate(value) => return *value,
-            Parameter::Position(addr) => *addr,
-            Parameter::Relative(offset) => (computer.relative_base + *offset) as usize,
-        };
-        return computer.memory.get(addr).map(|x| *x).unwrap_or(0);
+impl<V: Cell> Parameter<V> {
+    /// The parameter mode digit this parameter is encoded with.
+    pub fn mode(&self) -> isize {
+        match self {
+            Parameter::Position(_) => 0,
+            Parameter::Immediate(_) => 1,
+            Parameter::Relative(_) => 2,
+        }
+    }
+    /// The raw memory word following the instruction.
+    pub fn value(&self) -> V {
+        match self {
+            Parameter::Position(addr) => V::from_isize(*addr as isize),
+            Parameter::Immediate(value) | Parameter::Relative(value) => value.clone(),
+        }
+    }
+    fn address<M: Memory<V>>(&self, computer: &Computer<V, M>) -> Result<usize, ErrorKind<V>> {
+        match self {
+            Parameter::Position(addr) => Ok(*addr),
+            Parameter::Relative(offset) => {
+                let addr = computer
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(ErrorKind::Overflow)?;
+                to_address(&addr)
+            }
+            Parameter::Immediate(_) => Err(ErrorKind::WriteToImmediate),
+        }
     }
-    fn write(&self, computer: &mut Computer, value: isize) {
-        let addr = match self {
-            Parameter::Position(addr) => *addr,
-            Parameter::Relative(offset) => (computer.relative_base + *offset) as usize,
-            _ => panic!("writing to an Immediate parameter"),
+    fn write<M: Memory<V>>(
+        &self,
+        computer: &mut Computer<V, M>,
+        value: V,
+    ) -> Result<usize, ErrorKind<V>> {
+        let addr = self.address(computer)?;
+        computer.store(addr, value)?;
+        Ok(addr)
+    }
+}
+
+/// The operand values an instruction read, and the memory it wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Effects<V = isize> {
+    operands: [V; 3],
+    /// The address each operand was read from, `None` for immediate ones.
+    sources: [Option<usize>; 3],
+    operand_count: usize,
+    /// The address and the new value.
+    pub write: Option<(usize, V)>,
+    /// Whether anything wants to see the effects. If not, only the operands
+    /// of extensions are kept, as their semantics need them.
+    recording: bool,
+}
+
+impl<V: Clone> Effects<V> {
+    pub fn operands(&self) -> &[V] {
+        &self.operands[..self.operand_count]
+    }
+
+    pub fn sources(&self) -> &[Option<usize>] {
+        &self.sources[..self.operand_count]
+    }
+}
+
+impl<V: Cell> Effects<V> {
+    /// Reads an operand, and remembers its value and where it came from.
+    fn read<M: Memory<V>>(
+        &mut self,
+        param: &Parameter<V>,
+        computer: &Computer<V, M>,
+    ) -> Result<V, ErrorKind<V>> {
+        let (value, source) = match param {
+            Parameter::Immediate(value) => (value.clone(), None),
+            _ => {
+                let addr = param.address(computer)?;
+                (computer.peek(addr), Some(addr))
+            }
         };
-        computer
-            .memory
-            .resize(computer.memory.len().max(addr + 1), 0);
-        computer.memory[addr] = value;
+        if self.recording {
+            self.operands[self.operand_count] = value.clone();
+            self.sources[self.operand_count] = source;
+            self.operand_count += 1;
+        }
+        Ok(value)
     }
+
+    /// Writes through a parameter, and remembers where and what.
+    fn store<M: Memory<V>>(
+        &mut self,
+        param: &Parameter<V>,
+        computer: &mut Computer<V, M>,
+        value: V,
+    ) -> Result<(), ErrorKind<V>> {
+        if self.recording {
+            self.write = Some((param.write(computer, value.clone())?, value));
+        } else {
+            param.write(computer, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Gets notified around every instruction a `Computer` executes.
+///
+/// Observers are passed to `Computer::run_observed` by generic type, so the
+/// default no-op methods are inlined away and an unobserved `run` pays
+/// nothing for this.
+pub trait Observer<V = isize> {
+    /// Whether `after` looks at its `Effects`. If not, they are left empty,
+    /// which saves copying every operand.
+    const WANTS_EFFECTS: bool = true;
+    /// Called before executing `inst`, which was decoded at `pc`.
+    fn before(&mut self, _pc: usize, _inst: &Instruction<V>) {}
+    /// Called after `inst` has executed. Like `Computer::cycles`, this
+    /// leaves out halting, and an input instruction waiting for input.
+    fn after(&mut self, _pc: usize, _inst: &Instruction<V>, _effects: &Effects<V>) {}
+}
+
+/// The observer that does nothing.
+impl<V> Observer<V> for () {
+    const WANTS_EFFECTS: bool = false;
 }
 
-enum ArithmeticOp {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArithmeticOp {
     Add,
     Multiply,
 }
 
-enum JumpOp {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpOp {
     IfNotZero,
     IfZero,
 }
 
-enum RelationOp {
+impl JumpOp {
+    /// Whether the jump is taken when its first parameter is `value`.
+    pub fn taken<V: Cell>(&self, value: &V) -> bool {
+        match self {
+            JumpOp::IfNotZero => !value.is_zero(),
+            JumpOp::IfZero => value.is_zero(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelationOp {
     LessThan,
     Equal,
 }
 
-enum Instruction {
-    Arithmetic(ArithmeticOp, Parameter, Parameter, Parameter),
-    Input(Parameter),
-    Output(Parameter),
-    Jump(JumpOp, Parameter, Parameter),
-    Relation(RelationOp, Parameter, Parameter, Parameter),
-    AdjustBase(Parameter),
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction<V = isize> {
+    Arithmetic(ArithmeticOp, Parameter<V>, Parameter<V>, Parameter<V>),
+    Input(Parameter<V>),
+    Output(Parameter<V>),
+    Jump(JumpOp, Parameter<V>, Parameter<V>),
+    Relation(RelationOp, Parameter<V>, Parameter<V>, Parameter<V>),
+    AdjustBase(Parameter<V>),
     Halt,
+    /// An instruction registered in an `InstructionSet`. It is boxed to
+    /// keep the built-in instructions small, which are cloned a lot.
+    Extension(Box<Custom<V>>),
+}
+
+/// The opcode, mnemonic and parameters of an `Instruction::Extension`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Custom<V = isize> {
+    pub opcode: isize,
+    pub mnemonic: &'static str,
+    pub parameters: Vec<Parameter<V>>,
 }
 
+/// The number of words of the longest instruction.
+const MAX_WIDTH: usize = 4;
+
+/// Instructions at or above this address are not cached, so that jumping
+/// far away does not allocate a huge cache.
+const DECODE_CACHE_LIMIT: usize = 1 << 16;
+
+/// How many instructions are decoded before the cache is used. Filling the
+/// cache does not pay off for short runs, like the amplifiers of day7.
+const DECODE_CACHE_WARMUP: usize = 1000;
+
+/// The memory limit of a new `Computer`, in cells. It is far above what
+/// the puzzles need, but keeps a write to a huge address from aborting
+/// the process while allocating.
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
 enum InstructionResult {
     Waiting,
     Normal(usize),
     Jump(usize),
     Halt,
+    /// Moves on by the width, and stops running.
+    Trap(usize),
 }
 
-impl Instruction {
-    fn decode(memory: &[isize], offset: usize) -> Instruction {
-        let code = memory[offset] % 100;
-        let param_modes = memory[offset] / 100;
+impl<V: Cell> Instruction<V> {
+    /// Decodes the instruction at `offset` with the built-in opcodes.
+    pub fn decode(memory: &[V], offset: usize) -> Result<Instruction<V>, ErrorKind<V>> {
+        Self::decode_with(memory, offset, &InstructionSet::default())
+    }
+
+    /// Like `decode`, but with the opcodes of `set`.
+    pub fn decode_with(
+        memory: &[V],
+        offset: usize,
+        set: &InstructionSet<V>,
+    ) -> Result<Instruction<V>, ErrorKind<V>> {
+        Self::decode_from(
+            set,
+            |addr| memory.get(addr).cloned().unwrap_or_default(),
+            offset,
+        )
+    }
+
+    /// Like `decode_with`, but reads the memory words through `load`.
+    fn decode_from(
+        set: &InstructionSet<V>,
+        load: impl Fn(usize) -> V,
+        offset: usize,
+    ) -> Result<Instruction<V>, ErrorKind<V>> {
+        let word = load(offset);
+        let word = word
+            .to_isize()
+            .ok_or_else(|| ErrorKind::InvalidOpcode(word.clone()))?;
+        let code = word % 100;
+        let param_modes = word / 100;
 
         let decode_param = |n: u32| {
-            let value = memory[offset + n as usize];
+            let value = load(offset + n as usize);
             let mode = param_modes / (10isize.pow(n - 1)) % 10;
             match mode {
-                0 => Parameter::Position(value as usize),
-                1 => Parameter::Immediate(value),
-                2 => Parameter::Relative(value as isize),
-                _ => panic!("unknown parameter mode"),
+                0 => Ok(Parameter::Position(to_address(&value)?)),
+                1 => Ok(Parameter::Immediate(value)),
+                2 => Ok(Parameter::Relative(value)),
+                _ => Err(ErrorKind::InvalidParameterMode(mode)),
             }
         };
-
-        match code {
+        if !set.is_builtin(code) {
+            let extension = set
+                .extension(code)
+                .ok_or_else(|| ErrorKind::InvalidOpcode(V::from_isize(code)))?;
+            return Ok(Instruction::Extension(Box::new(Custom {
+                opcode: code,
+                mnemonic: extension.mnemonic,
+                parameters: (1..=extension.parameters.len() as u32)
+                    .map(decode_param)
+                    .collect::<Result<_, _>>()?,
+            })));
+        }
+        Ok(match code {
             1 | 2 => {
                 let op = if code == 1 {
                     ArithmeticOp::Add
                 } else {
                     ArithmeticOp::Multiply
                 };
-                Instruction::Arithmetic(op, decode_param(1), decode_param(2), decode_param(3))
+                Instruction::Arithmetic(op, decode_param(1)?, decode_param(2)?, decode_param(3)?)
             }
-            3 => Instruction::Input(decode_param(1)),
-            4 => Instruction::Output(decode_param(1)),
+            3 => Instruction::Input(decode_param(1)?),
+            4 => Instruction::Output(decode_param(1)?),
             5 | 6 => {
                 let op = if code == 5 {
                     JumpOp::IfNotZero
                 } else {
                     JumpOp::IfZero
                 };
-                Instruction::Jump(op, decode_param(1), decode_param(2))
+                Instruction::Jump(op, decode_param(1)?, decode_param(2)?)
             }
             7 | 8 => {
                 let op = if code == 7 {
@@ -101,136 +473,635 @@ impl Instruction {
                 } else {
                     RelationOp::Equal
                 };
-                Instruction::Relation(op, decode_param(1), decode_param(2), decode_param(3))
+                Instruction::Relation(op, decode_param(1)?, decode_param(2)?, decode_param(3)?)
             }
-            9 => Instruction::AdjustBase(decode_param(1)),
+            9 => Instruction::AdjustBase(decode_param(1)?),
             99 => Instruction::Halt,
-            _ => panic!("invalid opcode"),
+            _ => return Err(ErrorKind::InvalidOpcode(V::from_isize(code))),
+        })
+    }
+
+    pub fn opcode(&self) -> isize {
+        match self {
+            Instruction::Arithmetic(ArithmeticOp::Add, ..) => 1,
+            Instruction::Arithmetic(ArithmeticOp::Multiply, ..) => 2,
+            Instruction::Input(_) => 3,
+            Instruction::Output(_) => 4,
+            Instruction::Jump(JumpOp::IfNotZero, ..) => 5,
+            Instruction::Jump(JumpOp::IfZero, ..) => 6,
+            Instruction::Relation(RelationOp::LessThan, ..) => 7,
+            Instruction::Relation(RelationOp::Equal, ..) => 8,
+            Instruction::AdjustBase(_) => 9,
+            Instruction::Halt => 99,
+            Instruction::Extension(custom) => custom.opcode,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Arithmetic(ArithmeticOp::Add, ..) => "ADD",
+            Instruction::Arithmetic(ArithmeticOp::Multiply, ..) => "MUL",
+            Instruction::Input(_) => "IN",
+            Instruction::Output(_) => "OUT",
+            Instruction::Jump(JumpOp::IfNotZero, ..) => "JNZ",
+            Instruction::Jump(JumpOp::IfZero, ..) => "JZ",
+            Instruction::Relation(RelationOp::LessThan, ..) => "LT",
+            Instruction::Relation(RelationOp::Equal, ..) => "EQ",
+            Instruction::AdjustBase(_) => "ARB",
+            Instruction::Halt => "HLT",
+            Instruction::Extension(custom) => custom.mnemonic,
         }
     }
 
-    fn execute(&self, computer: &mut Computer) -> InstructionResult {
+    pub fn parameters(&self) -> Vec<&Parameter<V>> {
         match self {
+            Instruction::Arithmetic(_, a, b, c) | Instruction::Relation(_, a, b, c) => {
+                vec![a, b, c]
+            }
+            Instruction::Jump(_, a, b) => vec![a, b],
+            Instruction::Input(a) | Instruction::Output(a) | Instruction::AdjustBase(a) => {
+                vec![a]
+            }
+            Instruction::Halt => vec![],
+            Instruction::Extension(custom) => custom.parameters.iter().collect(),
+        }
+    }
+
+    /// The number of memory words this instruction occupies.
+    pub fn width(&self) -> usize {
+        1 + self.parameters().len()
+    }
+
+    /// The inverse of `decode`.
+    pub fn encode(&self) -> Vec<V> {
+        let params = self.parameters();
+        let modes = params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p.mode() * 10isize.pow(i as u32 + 2))
+            .sum::<isize>();
+        let mut words = vec![V::from_isize(self.opcode() + modes)];
+        words.extend(params.iter().map(|p| p.value()));
+        words
+    }
+
+    fn execute<M: Memory<V>, I: Io<V>>(
+        &self,
+        computer: &mut Computer<V, M>,
+        io: &mut I,
+        effects: &mut Effects<V>,
+    ) -> Result<InstructionResult, ErrorKind<V>> {
+        Ok(match self {
             Instruction::Halt => InstructionResult::Halt,
-            Instruction::Input(p) => match computer.input.pop_front() {
+            Instruction::Input(p) => match io.input() {
                 Some(value) => {
-                    p.write(computer, value);
+                    effects.store(p, computer, value)?;
                     InstructionResult::Normal(2)
                 }
                 None => InstructionResult::Waiting,
             },
             Instruction::Output(p) => {
-                computer.output.push_back(p.read(computer));
+                let value = effects.read(p, computer)?;
+                io.output(value);
                 InstructionResult::Normal(2)
             }
             Instruction::Arithmetic(op, lhs, rhs, dst) => {
-                let lhs = lhs.read(computer);
-                let rhs = rhs.read(computer);
+                let lhs = effects.read(lhs, computer)?;
+                let rhs = effects.read(rhs, computer)?;
                 let result = match op {
-                    ArithmeticOp::Add => lhs + rhs,
-                    ArithmeticOp::Multiply => lhs * rhs,
-                };
-                dst.write(computer, result);
+                    ArithmeticOp::Add => lhs.checked_add(&rhs),
+                    ArithmeticOp::Multiply => lhs.checked_mul(&rhs),
+                }
+                .ok_or(ErrorKind::Overflow)?;
+                effects.store(dst, computer, result)?;
                 InstructionResult::Normal(4)
             }
             Instruction::Relation(op, lhs, rhs, dst) => {
-                let lhs = lhs.read(computer);
-                let rhs = rhs.read(computer);
-                let result = match op {
+                let lhs = effects.read(lhs, computer)?;
+                let rhs = effects.read(rhs, computer)?;
+                let result = V::from_isize(match op {
                     RelationOp::Equal if lhs == rhs => 1,
                     RelationOp::LessThan if lhs < rhs => 1,
                     _ => 0,
-                };
-                dst.write(computer, result);
+                });
+                effects.store(dst, computer, result)?;
                 InstructionResult::Normal(4)
             }
             Instruction::Jump(op, value, jump) => {
-                let value = value.read(computer);
-                let jump = jump.read(computer) as usize;
-                match op {
-                    JumpOp::IfNotZero if value != 0 => InstructionResult::Jump(jump),
-                    JumpOp::IfZero if value == 0 => InstructionResult::Jump(jump),
-                    _ => InstructionResult::Normal(3),
+                let value = effects.read(value, computer)?;
+                let jump = effects.read(jump, computer)?;
+                // the target only has to be an address if it is used
+                if op.taken(&value) {
+                    InstructionResult::Jump(to_address(&jump)?)
+                } else {
+                    InstructionResult::Normal(3)
                 }
             }
             Instruction::AdjustBase(value) => {
-                computer.relative_base += value.read(computer);
+                let value = effects.read(value, computer)?;
+                computer.relative_base = computer
+                    .relative_base
+                    .checked_add(&value)
+                    .ok_or(ErrorKind::Overflow)?;
                 InstructionResult::Normal(2)
             }
+            Instruction::Extension(custom) => custom.execute(computer, effects)?,
+        })
+    }
+}
+
+impl<V: Cell> Custom<V> {
+    fn execute<M: Memory<V>>(
+        &self,
+        computer: &mut Computer<V, M>,
+        effects: &mut Effects<V>,
+    ) -> Result<InstructionResult, ErrorKind<V>> {
+        let extension = computer
+            .instructions
+            .extension(self.opcode)
+            .expect("decoded with the same instruction set");
+        let (access, semantics) = (extension.parameters, extension.semantics);
+        effects.recording = true;
+        let mut dst = None;
+        for (param, access) in self.parameters.iter().zip(access) {
+            match access {
+                Access::Read => {
+                    effects.read(param, computer)?;
+                }
+                Access::Write => dst = Some(param),
+            }
         }
+        let width = 1 + access.len();
+        Ok(match semantics(effects.operands())? {
+            Action::Continue => InstructionResult::Normal(width),
+            Action::Write(value) => {
+                let dst = dst.ok_or(ErrorKind::Extension("extension has no write parameter"))?;
+                effects.store(dst, computer, value)?;
+                InstructionResult::Normal(width)
+            }
+            Action::Jump(target) => InstructionResult::Jump(to_address(&target)?),
+            Action::Halt => InstructionResult::Halt,
+            Action::Trap => InstructionResult::Trap(width),
+        })
     }
 }
 
-pub enum RunResult {
-    Completed(isize),
+pub enum RunResult<V = isize> {
+    Completed(V),
+    Waiting,
+    /// The instruction budget ran out. Running again with a new budget
+    /// continues where the program left off.
+    OutOfBudget,
+    /// An extension instruction trapped. Running again continues after it.
+    Trapped,
+}
+
+/// What happened during a single `Computer::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// An instruction was executed, and the pc moved on.
+    Executed,
+    /// The pc is at an input instruction, but there is no input.
     Waiting,
+    /// The pc is at a halt instruction.
+    Halted,
+    /// The instruction budget is used up, so nothing was executed.
+    OutOfBudget,
+    /// An extension instruction was executed, which asks to stop here.
+    Trapped,
+}
+
+/// How many instructions a `Computer` executed, broken down by opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycles {
+    by_opcode: [u64; 100],
+}
+
+impl Default for Cycles {
+    fn default() -> Cycles {
+        Cycles {
+            by_opcode: [0; 100],
+        }
+    }
 }
 
-#[derive(Clone)]
-pub struct Computer {
-    memory: Vec<isize>,
+impl Cycles {
+    pub fn total(&self) -> u64 {
+        self.by_opcode.iter().sum()
+    }
+
+    pub fn get(&self, opcode: isize) -> u64 {
+        self.by_opcode.get(opcode as usize).copied().unwrap_or(0)
+    }
+
+    /// Takes back one execution of `opcode`, like when stepping back over it.
+    pub fn uncount(&mut self, opcode: isize) {
+        if let Some(count) = self.by_opcode.get_mut(opcode as usize) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// The opcodes that were executed at least once, with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (isize, u64)> + '_ {
+        self.by_opcode
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(opcode, count)| (opcode as isize, *count))
+    }
+}
+
+/// An Intcode computer, which stores values of type `V` in its memory cells.
+///
+/// The default `isize` is what all the puzzles need. Other `Cell` types
+/// can be picked with `Computer::<i128>::parse` and the like.
+///
+/// The memory is a dense `Vec` by default. Programs that write to far-away
+/// addresses can use `Computer::<isize, PagedMemory<isize>>` instead.
+///
+/// The opcodes come from an `InstructionSet`, which can be changed with
+/// `set_instruction_set` to run Intcode variants with extra instructions.
+///
+/// Once a program has run for a while, decoded instructions are cached by
+/// their address, and every write to memory drops the cached instructions
+/// it overlaps.
+#[derive(Debug, Clone)]
+pub struct Computer<V = isize, M = Vec<V>> {
+    memory: M,
+    instructions: Arc<InstructionSet<V>>,
+    /// `None` when the cache is turned off.
+    decoded: Option<Vec<Option<Instruction<V>>>>,
+    /// How many instructions were decoded without the cache, up to
+    /// `DECODE_CACHE_WARMUP`.
+    uncached_decodes: usize,
+    memory_limit: Option<usize>,
+    budget: Option<u64>,
+    cycles: Cycles,
     pc: usize,
-    relative_base: isize,
-    input: VecDeque<isize>,
-    output: VecDeque<isize>,
+    relative_base: V,
+    input: VecDeque<V>,
+    output: VecDeque<V>,
+}
+
+/// Parses the comma-separated program text into memory words.
+pub fn parse_memory<V: Cell>(input: &str) -> Result<Vec<V>, Error<V>> {
+    input
+        .split(',')
+        .enumerate()
+        .map(|(pc, n)| {
+            n.trim().parse::<V>().map_err(|_| Error {
+                pc,
+                word: V::default(),
+                kind: ErrorKind::InvalidNumber(n.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// The inverse of `parse_memory`.
+pub fn format_memory<V: fmt::Display>(memory: &[V]) -> String {
+    memory
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 impl Computer {
-    pub fn new(memory: &str) -> Computer {
-        let memory = memory
-            .split(',')
-            .map(|n| n.parse::<isize>().expect("parsing number"))
-            .collect();
+    pub fn new(memory: &str) -> Result<Computer, Error> {
+        Self::parse(memory)
+    }
 
+    pub fn new_with_input(memory: &str, input: isize) -> Result<Computer, Error> {
+        let mut c = Self::new(memory)?;
+        c.input.push_back(input);
+        Ok(c)
+    }
+}
+
+impl<V: Cell, M: Memory<V>> Computer<V, M> {
+    pub fn parse(memory: &str) -> Result<Computer<V, M>, Error<V>> {
+        Ok(Self::from_memory(parse_memory(memory)?))
+    }
+
+    pub fn from_memory(memory: Vec<V>) -> Computer<V, M> {
         Computer {
-            memory,
+            memory: M::from_vec(memory),
+            instructions: Arc::new(InstructionSet::default()),
+            decoded: Some(vec![]),
+            uncached_decodes: 0,
+            memory_limit: Some(DEFAULT_MEMORY_LIMIT),
+            budget: None,
+            cycles: Cycles::default(),
             pc: 0,
-            relative_base: 0,
+            relative_base: V::default(),
             input: VecDeque::new(),
             output: VecDeque::new(),
         }
     }
 
-    pub fn new_with_input(memory: &str, input: isize) -> Computer {
-        let mut c = Self::new(memory);
-        c.input.push_back(input);
-        c
-    }
-
-    pub fn push_input(&mut self, input: isize) {
+    pub fn push_input(&mut self, input: V) {
         self.input.push_back(input);
     }
 
-    pub fn pop_output(&mut self) -> Option<isize> {
+    pub fn pop_output(&mut self) -> Option<V> {
         self.output.pop_front()
     }
 
-    #[cfg(test)]
-    pub fn output(&self) -> &VecDeque<isize> {
+    pub fn input(&self) -> &VecDeque<V> {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut VecDeque<V> {
+        &mut self.input
+    }
+
+    pub fn output(&self) -> &VecDeque<V> {
         &self.output
     }
 
-    pub fn run(&mut self) -> RunResult {
-        loop {
-            let inst = Instruction::decode(&self.memory, self.pc);
-            let result = inst.execute(self);
-            match result {
-                InstructionResult::Waiting => return RunResult::Waiting,
-                InstructionResult::Halt => {
-                    let most_recent_output = *self.output.back().expect("halting without output");
-
-                    return RunResult::Completed(most_recent_output);
+    pub fn output_mut(&mut self) -> &mut VecDeque<V> {
+        &mut self.output
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn set_memory(&mut self, memory: Vec<V>) {
+        self.memory = M::from_vec(memory);
+        if let Some(decoded) = &mut self.decoded {
+            decoded.clear();
+        }
+    }
+
+    /// Turns the cache of decoded instructions on or off. It is on by
+    /// default, and only worth turning off to compare the speed.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decoded = if enabled { Some(vec![]) } else { None };
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet<V> {
+        &self.instructions
+    }
+
+    /// Replaces the opcodes the computer understands, which are the built-in
+    /// ones by default.
+    pub fn set_instruction_set(&mut self, set: InstructionSet<V>) {
+        self.instructions = Arc::new(set);
+        if let Some(decoded) = &mut self.decoded {
+            decoded.clear();
+        }
+    }
+
+    /// Limits the number of memory cells a running program may allocate.
+    /// The default is `DEFAULT_MEMORY_LIMIT`, and `None` means no limit.
+    pub fn set_memory_limit(&mut self, cells: Option<usize>) {
+        self.memory_limit = cells;
+    }
+
+    /// Reads memory at `addr`, which is `0` outside of the loaded program.
+    pub fn peek(&self, addr: usize) -> V {
+        self.memory.load(addr)
+    }
+
+    /// Writes memory at `addr`, growing the memory if needed. Unlike writes
+    /// by the program itself, this ignores the memory limit.
+    pub fn poke(&mut self, addr: usize, value: V) {
+        self.invalidate(addr);
+        self.memory.store(addr, value);
+    }
+
+    fn store(&mut self, addr: usize, value: V) -> Result<(), ErrorKind<V>> {
+        match self.memory_limit {
+            Some(limit) if self.memory.allocated_after_store(addr) > limit => {
+                Err(ErrorKind::MemoryLimit(addr))
+            }
+            _ => {
+                self.invalidate(addr);
+                self.memory.store(addr, value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Drops the cached instructions that include the word at `addr`.
+    fn invalidate(&mut self, addr: usize) {
+        if let Some(decoded) = &mut self.decoded {
+            let start = addr.saturating_sub(MAX_WIDTH - 1).min(decoded.len());
+            let end = (addr + 1).min(decoded.len());
+            for slot in &mut decoded[start..end] {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Decodes the instruction at `pc`, or takes it from the cache.
+    fn decode(&mut self, pc: usize) -> Result<Instruction<V>, ErrorKind<V>> {
+        if let Some(Some(inst)) = self.decoded.as_ref().and_then(|d| d.get(pc)) {
+            return Ok(inst.clone());
+        }
+        let inst = Instruction::decode_from(&self.instructions, |addr| self.memory.load(addr), pc)?;
+        if self.uncached_decodes < DECODE_CACHE_WARMUP {
+            self.uncached_decodes += 1;
+            return Ok(inst);
+        }
+        match &mut self.decoded {
+            Some(decoded) if pc < DECODE_CACHE_LIMIT => {
+                if pc >= decoded.len() {
+                    decoded.resize(pc + 1, None);
                 }
-                InstructionResult::Normal(width) => self.pc += width,
-                InstructionResult::Jump(to) => self.pc = to,
+                decoded[pc] = Some(inst.clone());
+            }
+            _ => {}
+        }
+        Ok(inst)
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub fn relative_base(&self) -> V {
+        self.relative_base.clone()
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: V) {
+        self.relative_base = relative_base;
+    }
+
+    /// The number of instructions that may still be executed, if limited.
+    pub fn budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    /// Limits how many more instructions get executed. Once the budget is
+    /// used up, running stops with `RunResult::OutOfBudget`.
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    /// The instructions executed so far. Neither halting nor waiting for
+    /// input counts as an instruction.
+    pub fn cycles(&self) -> &Cycles {
+        &self.cycles
+    }
+
+    pub fn cycles_mut(&mut self) -> &mut Cycles {
+        &mut self.cycles
+    }
+
+    pub fn reset_cycles(&mut self) {
+        self.cycles = Cycles::default();
+    }
+
+    fn fault(&self, kind: ErrorKind<V>) -> Error<V> {
+        Error {
+            pc: self.pc,
+            word: self.peek(self.pc),
+            kind,
+        }
+    }
+
+    /// Executes the single instruction at the pc.
+    pub fn step(&mut self) -> Result<Step, Error<V>> {
+        self.step_observed(&mut ())
+    }
+
+    /// Like `step`, but notifies `observer` about the executed instruction.
+    pub fn step_observed<O: Observer<V>>(&mut self, observer: &mut O) -> Result<Step, Error<V>> {
+        self.with_queues(|c, queues| c.step_with(queues, observer))
+    }
+
+    /// Runs `f` with the input and output queues moved out into `Queues`.
+    fn with_queues<T>(&mut self, f: impl FnOnce(&mut Self, &mut Queues<V>) -> T) -> T {
+        let mut queues = Queues {
+            input: std::mem::take(&mut self.input),
+            output: std::mem::take(&mut self.output),
+        };
+        let result = f(self, &mut queues);
+        self.input = queues.input;
+        self.output = queues.output;
+        result
+    }
+
+    /// Like `step_observed`, but does its input and output through `io`
+    /// instead of the queues.
+    pub fn step_with<I: Io<V>, O: Observer<V>>(
+        &mut self,
+        io: &mut I,
+        observer: &mut O,
+    ) -> Result<Step, Error<V>> {
+        let pc = self.pc;
+        let decoded = self.decode(pc);
+        // halting is free, so it does not need any budget left
+        if self.budget == Some(0) && !matches!(decoded, Ok(Instruction::Halt)) {
+            return Ok(Step::OutOfBudget);
+        }
+        let inst = decoded.map_err(|k| self.fault(k))?;
+        observer.before(pc, &inst);
+        let mut effects = Effects {
+            recording: O::WANTS_EFFECTS,
+            ..Effects::default()
+        };
+        let result = inst
+            .execute(self, io, &mut effects)
+            .map_err(|k| self.fault(k))?;
+        let step = match result {
+            InstructionResult::Waiting => return Ok(Step::Waiting),
+            InstructionResult::Halt => Step::Halted,
+            InstructionResult::Normal(width) => {
+                self.pc += width;
+                Step::Executed
+            }
+            InstructionResult::Jump(to) => {
+                self.pc = to;
+                Step::Executed
             }
+            InstructionResult::Trap(width) => {
+                self.pc += width;
+                Step::Trapped
+            }
+        };
+        // a halted program stays at its halt instruction, so running it again
+        // must not use up or count anything
+        if step != Step::Halted {
+            if let Some(budget) = &mut self.budget {
+                *budget -= 1;
+            }
+            self.cycles.by_opcode[inst.opcode() as usize] += 1;
+            observer.after(pc, &inst, &effects);
         }
+        Ok(step)
     }
 
-    pub fn run_to_halt(&mut self) -> isize {
-        match self.run() {
-            RunResult::Completed(value) => value,
-            RunResult::Waiting => panic!("expected computer to complete"),
+    pub fn run(&mut self) -> Result<RunResult<V>, Error<V>> {
+        self.run_observed(&mut ())
+    }
+
+    /// Like `run`, but notifies `observer` about every executed instruction.
+    pub fn run_observed<O: Observer<V>>(
+        &mut self,
+        observer: &mut O,
+    ) -> Result<RunResult<V>, Error<V>> {
+        match self.resume_observed(observer)? {
+            Step::Halted => {
+                let most_recent_output = self
+                    .output
+                    .back()
+                    .cloned()
+                    .ok_or_else(|| self.fault(ErrorKind::HaltWithoutOutput))?;
+
+                Ok(RunResult::Completed(most_recent_output))
+            }
+            Step::OutOfBudget => Ok(RunResult::OutOfBudget),
+            Step::Trapped => Ok(RunResult::Trapped),
+            _ => Ok(RunResult::Waiting),
+        }
+    }
+
+    /// Runs until the program halts, waits for input, runs out of budget or
+    /// traps, returning which of these happened. Unlike `run`, this is fine
+    /// with programs that do not output anything, and only leave their
+    /// result in memory.
+    pub fn resume(&mut self) -> Result<Step, Error<V>> {
+        self.resume_observed(&mut ())
+    }
+
+    /// Like `resume`, but notifies `observer` about every executed instruction.
+    pub fn resume_observed<O: Observer<V>>(&mut self, observer: &mut O) -> Result<Step, Error<V>> {
+        self.with_queues(|c, queues| c.resume_with(queues, observer))
+    }
+
+    /// Like `resume_observed`, but does its input and output through `io`
+    /// instead of the queues.
+    pub fn resume_with<I: Io<V>, O: Observer<V>>(
+        &mut self,
+        io: &mut I,
+        observer: &mut O,
+    ) -> Result<Step, Error<V>> {
+        loop {
+            match self.step_with(io, observer)? {
+                Step::Executed => {}
+                stop => return Ok(stop),
+            }
+        }
+    }
+
+    /// Runs until the program halts, and returns its last output. Traps
+    /// are run through.
+    pub fn run_to_halt(&mut self) -> Result<V, Error<V>> {
+        loop {
+            match self.run()? {
+                RunResult::Completed(value) => return Ok(value),
+                RunResult::Waiting => return Err(self.fault(ErrorKind::MissingInput)),
+                RunResult::OutOfBudget => return Err(self.fault(ErrorKind::OutOfBudget)),
+                RunResult::Trapped => {}
+            }
         }
     }
 }
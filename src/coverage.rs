@@ -0,0 +1,257 @@
+use crate::cell::Cell;
+use crate::computer::{Effects, Instruction, Observer};
+use crate::disasm::{decode_at, Line};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+#[test]
+fn test_coverage() {
+    use crate::asm::assemble;
+    use crate::computer::Computer;
+
+    let program = assemble(
+        "
+            IN [x]
+            JZ [x], #zero
+            OUT [x]
+            HLT
+        zero:
+            OUT #0
+            HLT
+        x:  db 0
+        ",
+    )
+    .unwrap();
+    let run = |input: isize| {
+        let mut computer: Computer = Computer::from_memory(program.clone());
+        computer.push_input(input);
+        let mut coverage = Coverage::new();
+        computer.run_observed(&mut coverage).unwrap();
+        coverage
+    };
+
+    let mut coverage = run(5);
+    assert_eq!(coverage.count(8), 0);
+    assert_eq!(coverage.branch(2), Some((0, 1)));
+    assert_eq!(
+        coverage.summary(&program).to_string(),
+        "instructions: 4/6 (66.7%)\nbranches: 1/2 (50.0%)\nmemory: 1 cells read, 1 written\n"
+    );
+
+    // running a halted program again covers nothing new
+    let mut computer: Computer = Computer::from_memory(program.clone());
+    computer.push_input(5);
+    let mut again = Coverage::new();
+    for _ in 0..2 {
+        computer.run_observed(&mut again).unwrap();
+    }
+    assert_eq!(again, coverage);
+
+    coverage.merge(&run(0));
+    assert_eq!(coverage.count(0), 2);
+    assert_eq!(coverage.branch(2), Some((1, 1)));
+    assert_eq!(
+        coverage.listing(&program),
+        "  count  rw  addr  instruction
+      2  ..     0: IN   [11]
+      2  ..     2: JZ   [11], #8  (taken 1, not taken 1)
+      1  ..     5: OUT  [11]
+      1  ..     7: HLT
+      1  ..     8: OUT  #0
+      1  ..    10: HLT
+      -  rw    11: db   0
+"
+    );
+}
+
+/// Collects which instructions ran, which way every branch went, and which
+/// memory was read and written, over one or more runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// How often each address was executed as an instruction.
+    executed: BTreeMap<usize, u64>,
+    /// How often each jump was taken and not taken.
+    branches: BTreeMap<usize, (u64, u64)>,
+    read: BTreeSet<usize>,
+    written: BTreeSet<usize>,
+}
+
+/// The percentages `Coverage::summary` reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// Executed and all instructions.
+    pub instructions: (usize, usize),
+    /// Branch directions that were taken, and all of them.
+    pub branches: (usize, usize),
+    pub read: usize,
+    pub written: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |(covered, total): (usize, usize)| {
+            format!(
+                "{}/{} ({:.1}%)",
+                covered,
+                total,
+                100.0 * covered as f64 / total.max(1) as f64
+            )
+        };
+        writeln!(f, "instructions: {}", percent(self.instructions))?;
+        writeln!(f, "branches: {}", percent(self.branches))?;
+        writeln!(
+            f,
+            "memory: {} cells read, {} written",
+            self.read, self.written
+        )
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// How often the instruction at `pc` was executed.
+    pub fn count(&self, pc: usize) -> u64 {
+        self.executed.get(&pc).copied().unwrap_or(0)
+    }
+
+    /// How often the jump at `pc` was taken and not taken, if it ran.
+    pub fn branch(&self, pc: usize) -> Option<(u64, u64)> {
+        self.branches.get(&pc).copied()
+    }
+
+    pub fn was_read(&self, addr: usize) -> bool {
+        self.read.contains(&addr)
+    }
+
+    pub fn was_written(&self, addr: usize) -> bool {
+        self.written.contains(&addr)
+    }
+
+    /// Adds the results of another run.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (pc, count) in &other.executed {
+            *self.executed.entry(*pc).or_insert(0) += count;
+        }
+        for (pc, (taken, not_taken)) in &other.branches {
+            let branch = self.branches.entry(*pc).or_insert((0, 0));
+            branch.0 += taken;
+            branch.1 += not_taken;
+        }
+        self.read.extend(&other.read);
+        self.written.extend(&other.written);
+    }
+
+    /// Disassembles `memory` the way it was executed: from every executed
+    /// address as an instruction, and elsewhere like `disasm` does.
+    fn lines(&self, memory: &[isize]) -> Vec<Line> {
+        let mut lines = vec![];
+        let mut addr = 0;
+        while addr < memory.len() {
+            let executed = self.executed.contains_key(&addr);
+            let line = match Instruction::decode(memory, addr) {
+                Ok(inst) if executed => Line::Instruction(addr, inst),
+                // a linear sweep must not run across an executed address
+                _ => match decode_at(memory, addr) {
+                    Some(inst)
+                        if !(addr + 1..addr + inst.width())
+                            .any(|a| self.executed.contains_key(&a)) =>
+                    {
+                        Line::Instruction(addr, inst)
+                    }
+                    _ => Line::Data(addr, memory[addr]),
+                },
+            };
+            addr += line.width();
+            lines.push(line);
+        }
+        lines
+    }
+
+    pub fn summary(&self, memory: &[isize]) -> Summary {
+        let mut summary = Summary {
+            instructions: (0, 0),
+            branches: (0, 0),
+            read: self.read.len(),
+            written: self.written.len(),
+        };
+        for line in self.lines(memory) {
+            if let Line::Instruction(pc, inst) = line {
+                summary.instructions.0 += usize::from(self.count(pc) > 0);
+                summary.instructions.1 += 1;
+                if let Instruction::Jump(..) = inst {
+                    let (taken, not_taken) = self.branch(pc).unwrap_or((0, 0));
+                    summary.branches.0 += usize::from(taken > 0) + usize::from(not_taken > 0);
+                    summary.branches.1 += 2;
+                }
+            }
+        }
+        summary
+    }
+
+    /// An annotated disassembly of `memory`, with execution counts, `#####`
+    /// for instructions that never ran, whether each cell was read or
+    /// written, and which way branches went.
+    pub fn listing(&self, memory: &[isize]) -> String {
+        let mut out = String::from("  count  rw  addr  instruction\n");
+        for line in self.lines(memory) {
+            let addr = line.address();
+            let count = match line {
+                Line::Instruction(pc, _) if self.count(pc) > 0 => self.count(pc).to_string(),
+                Line::Instruction(..) => "#####".to_string(),
+                Line::Data(..) => "-".to_string(),
+            };
+            // reads and writes anywhere in the instruction
+            let cells = addr..addr + line.width();
+            let read = if cells.clone().any(|a| self.was_read(a)) {
+                'r'
+            } else {
+                '.'
+            };
+            let written = if cells.clone().any(|a| self.was_written(a)) {
+                'w'
+            } else {
+                '.'
+            };
+            write!(out, "{:>7}  {}{} {}", count, read, written, line).unwrap();
+            if let Line::Instruction(pc, Instruction::Jump(..)) = line {
+                match self.branch(pc) {
+                    Some((taken, not_taken)) => {
+                        write!(out, "  (taken {}, not taken {})", taken, not_taken).unwrap()
+                    }
+                    None => write!(out, "  (never reached)").unwrap(),
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl<V: Cell> Observer<V> for Coverage {
+    /// Halting does not count as executing, but a halt that was reached is
+    /// covered, however often a halted program is run again.
+    fn before(&mut self, pc: usize, inst: &Instruction<V>) {
+        if let Instruction::Halt = inst {
+            self.executed.entry(pc).or_insert(1);
+        }
+    }
+
+    fn after(&mut self, pc: usize, inst: &Instruction<V>, effects: &Effects<V>) {
+        *self.executed.entry(pc).or_insert(0) += 1;
+        self.read.extend(effects.sources().iter().flatten());
+        if let Some((addr, _)) = &effects.write {
+            self.written.insert(*addr);
+        }
+        if let Instruction::Jump(op, ..) = inst {
+            let branch = self.branches.entry(pc).or_insert((0, 0));
+            if op.taken(&effects.operands()[0]) {
+                branch.0 += 1;
+            } else {
+                branch.1 += 1;
+            }
+        }
+    }
+}
@@ -1,93 +1,69 @@
-enum OpCode {
-    // Add = 1,
-// Multiply = 2,
-// End = 99,
-}
-
-impl OpCode {
-    fn execute_at(memory: &mut [usize], pc: usize) -> usize {
-        // TODO: maybe convert this to a real enum?
-        let opcode = memory[pc];
-        // meh… can I simplify this somehow?
-        match opcode {
-            1 => {
-                if let [1, lhs, rhs, ret] = memory[pc..pc + 4] {
-                    memory[ret] = memory[lhs] + memory[rhs];
-                }
-                4
-            }
-            2 => {
-                if let [2, lhs, rhs, ret] = memory[pc..pc + 4] {
-                    memory[ret] = memory[lhs] * memory[rhs];
-                }
-                4
-            }
-            _ => 0,
-        }
-    }
-}
-
-fn execute_program(memory: &mut [usize]) -> &[usize] {
-    let mut pc = 0;
-    loop {
-        let offset = OpCode::execute_at(memory, pc);
-        if offset == 0 {
-            return memory;
-        }
-        pc += offset;
-    }
-}
+use crate::computer::Computer;
+use crate::symbolic::{solve, Symbolic};
 
 #[test]
 fn test_day2() {
-    let mut mem = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+    fn run_test(mem: &str) -> Vec<isize> {
+        let mut c = Computer::new(mem).unwrap();
+        c.resume().unwrap();
+        c.memory().to_vec()
+    }
+
     assert_eq!(
-        execute_program(&mut mem),
+        run_test("1,9,10,3,2,3,11,0,99,30,40,50"),
         &[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]
     );
-
-    let mut mem = vec![1, 0, 0, 0, 99];
-    assert_eq!(execute_program(&mut mem), &[2, 0, 0, 0, 99]);
-
-    let mut mem = vec![2, 3, 0, 3, 99];
-    assert_eq!(execute_program(&mut mem), &[2, 3, 0, 6, 99]);
-
-    let mut mem = vec![2, 4, 4, 5, 99, 0];
-    assert_eq!(execute_program(&mut mem), &[2, 4, 4, 5, 99, 9801]);
-
-    let mut mem = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
-    assert_eq!(execute_program(&mut mem), &[30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    assert_eq!(run_test("1,0,0,0,99"), &[2, 0, 0, 0, 99]);
+    assert_eq!(run_test("2,3,0,3,99"), &[2, 3, 0, 6, 99]);
+    assert_eq!(run_test("2,4,4,5,99,0"), &[2, 4, 4, 5, 99, 9801]);
+    assert_eq!(
+        run_test("1,1,1,4,99,5,6,0,99"),
+        &[30, 1, 1, 4, 2, 5, 6, 0, 99]
+    );
 }
 
-fn read_mem(input: &str) -> Vec<usize> {
-    input
-        .split(',')
-        .map(|n| n.parse::<usize>().expect("parsing number"))
-        .collect()
+/// Patches in the noun and verb, runs the program, and returns what is left
+/// at position 0 after the program halts.
+fn run_with(computer: &Computer, noun: isize, verb: isize) -> isize {
+    let mut computer = computer.clone();
+    computer.poke(1, noun);
+    computer.poke(2, verb);
+    computer.resume().expect("running program");
+    computer.peek(0)
 }
 
 pub fn part1(input: &str) -> String {
-    let mut mem = read_mem(input);
+    let computer = Computer::new(input).expect("parsing program");
     // before running the program, replace position 1 with the value 12
     // and replace position 2 with the value 2.
-    mem[1] = 12;
-    mem[2] = 2;
-    execute_program(&mut mem);
-    // What value is left at position 0 after the program halts?
-    mem[0].to_string()
+    run_with(&computer, 12, 2).to_string()
+}
+
+/// Solves for the noun and verb with a single symbolic run, if the program
+/// is simple enough for that.
+fn solve_symbolically(computer: &Computer, target: isize) -> Option<(isize, isize)> {
+    let mut symbolic = Symbolic::new(computer.memory());
+    let noun = symbolic.symbolize(1, 0..=99);
+    let verb = symbolic.symbolize(2, 0..=99);
+    symbolic.run().ok()?;
+    let values = solve(symbolic.peek(0), target, symbolic.domains())?;
+    Some((values[noun], values[verb]))
 }
 
 pub fn part2(input: &str) -> String {
-    let mem = read_mem(input);
+    let computer = Computer::new(input).expect("parsing program");
+
+    // determine what pair of inputs produces the output 19690720.
+    if let Some((noun, verb)) = solve_symbolically(&computer, 19690720) {
+        if run_with(&computer, noun, verb) == 19690720 {
+            return (100 * noun + verb).to_string();
+        }
+    }
 
+    // otherwise, try them all
     for noun in 0..=99 {
         for verb in 0..=99 {
-            let mut mem = mem.clone();
-            mem[1] = noun;
-            mem[2] = verb;
-            execute_program(&mut mem);
-            // determine what pair of inputs produces the output 19690720.
-            if mem[0] == 19690720 {
+            if run_with(&computer, noun, verb) == 19690720 {
                 return (100 * noun + verb).to_string();
             }
         }
@@ -59,7 +59,7 @@ fn parse_path(input: &str) -> Path {
     input
         .split(',')
         .map(|c| Command {
-            direction: c.chars().nth(0).expect("expected char").into(),
+            direction: c.chars().next().expect("expected char").into(),
             length: c[1..].parse().expect("parsing number"),
         })
         .collect()
@@ -77,7 +77,7 @@ pub fn part1(input: &str) -> String {
     let origin = Point { x: 0, y: 0 };
     field.insert(origin.clone(), 0);
 
-    let mut min_distance = isize::max_value();
+    let mut min_distance = isize::MAX;
 
     let mut trace_path = |path: Path, id: usize| {
         let mut point = origin.clone();
@@ -120,7 +120,7 @@ pub fn part2(input: &str) -> String {
     let origin = Point { x: 0, y: 0 };
     field.insert(origin.clone(), (0, 0));
 
-    let mut min_steps = isize::max_value();
+    let mut min_steps = isize::MAX;
 
     let mut trace_path = |path: Path, id: usize| {
         let mut point = origin.clone();
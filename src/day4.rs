@@ -1,3 +1,4 @@
+#[allow(clippy::bool_assert_comparison)]
 #[test]
 fn test_day4() {
     assert_eq!(is_valid_password("111111"), true);
@@ -3,8 +3,8 @@ use crate::computer::Computer;
 #[test]
 fn test_day5() {
     fn run_test(mem: &str, input: isize) -> isize {
-        let mut c = Computer::new_with_input(mem, input);
-        c.run_to_halt()
+        let mut c = Computer::new_with_input(mem, input).unwrap();
+        c.run_to_halt().unwrap()
     }
 
     // equal to 8
@@ -41,13 +41,13 @@ fn test_day5() {
 }
 
 pub fn part1(input: &str) -> String {
-    let mut c = Computer::new_with_input(input, 1);
-    let result = c.run_to_halt();
+    let mut c = Computer::new_with_input(input, 1).expect("parsing program");
+    let result = c.run_to_halt().expect("running program");
     result.to_string()
 }
 
 pub fn part2(input: &str) -> String {
-    let mut c = Computer::new_with_input(input, 5);
-    let result = c.run_to_halt();
+    let mut c = Computer::new_with_input(input, 5).expect("parsing program");
+    let result = c.run_to_halt().expect("running program");
     result.to_string()
 }
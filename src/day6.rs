@@ -25,7 +25,7 @@ struct Object<'a> {
 fn traverse_subtree<'a>(
     // well… there was my first real fight with the borrow checker…
     // recursive graph-like structures sure are a pain to work with…
-    mut objects: &mut HashMap<&'a str, Object<'a>>,
+    objects: &mut HashMap<&'a str, Object<'a>>,
     node: &'a str,
     depth: usize,
 ) {
@@ -34,7 +34,7 @@ fn traverse_subtree<'a>(
     let children = node.children.clone(); // <- because we can’t have this borrow alive later :-(
 
     for child in &children {
-        traverse_subtree(&mut objects, child, depth + 1);
+        traverse_subtree(objects, child, depth + 1);
     }
 }
 
@@ -116,15 +116,14 @@ pub fn part2(input: &str) -> String {
     let mut path_to_you = trace_path(&objects, vec![], "YOU");
     let mut path_to_san = trace_path(&objects, vec![], "SAN");
 
-    let common_ancestor = path_to_san
+    let common_ancestor = *path_to_san
         .iter()
         .zip(path_to_you.iter())
         .take_while(|(a, b)| a == b)
         .last()
         .unwrap()
-        .0
-        .clone(); // <- say whaaaat? I need to clone the `&str` because otherwise
-                  // I can’t pop from `path_to_san`?
+        .0; // <- say whaaaat? I need to copy the `&str` out because otherwise
+            // I can’t pop from `path_to_san`?
 
     // and essentially, the number of traversals is:
     // depth of first parent of YOU relative to common ancestor
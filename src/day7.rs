@@ -1,4 +1,7 @@
-use crate::computer::{Computer, RunResult};
+use crate::computer::Computer;
+use crate::permute::par_max_by_key;
+use crate::pipeline::Pipeline;
+use std::thread;
 
 #[test]
 fn test_day7() {
@@ -26,111 +29,47 @@ fn test_day7() {
     );
 }
 
-// basically copy-pasta of https://en.wikipedia.org/wiki/Heap%27s_algorithm#Details_of_the_algorithm
-fn permute<O>(mut output: O, mut a: Vec<isize>)
-where
-    O: FnMut(Vec<isize>) -> (),
-{
-    let n = a.len();
-    //c is an encoding of the stack state. c[k] encodes the for-loop counter for when generate(k+1, A) is called
-    let mut c = vec![0; n];
-
-    output(a.clone());
-
-    //i acts similarly to the stack pointer
-    let mut i = 0;
-    while i < n {
-        if c[i] < i {
-            if (i % 2) == 0 {
-                a.swap(0, i);
-            } else {
-                a.swap(c[i], i);
-            }
-            output(a.clone());
-            //Swap has occurred ending the for-loop. Simulate the increment of the for-loop counter
-            c[i] += 1;
-            //Simulate recursive call reaching the base case by bringing the pointer to the base case analog in the array
-            i = 0;
-        } else {
-            //Calling generate(i+1, A) has ended as the for-loop terminated. Reset the state and simulate popping the stack by incrementing the pointer.
-            c[i] = 0;
-            i += 1;
-        }
+/// Runs one amplifier per phase setting, each feeding the next one, and
+/// returns the last output of the last amplifier. With `feedback`, that one
+/// also feeds back into the first.
+fn amplify(program: &Computer, phases: &[isize], feedback: bool) -> isize {
+    let mut pipeline = Pipeline::new();
+    let amplifiers: Vec<_> = phases
+        .iter()
+        .map(|phase| {
+            let amplifier = pipeline.add_node(program.clone());
+            pipeline.push_input(amplifier, *phase);
+            amplifier
+        })
+        .collect();
+    if feedback {
+        pipeline.ring(&amplifiers);
+    } else {
+        pipeline.chain(&amplifiers);
     }
+    pipeline.push_input(amplifiers[0], 0);
+
+    let report = pipeline.run().expect("running amplifier");
+    report
+        .last_output(amplifiers[amplifiers.len() - 1])
+        .expect("expected output")
 }
 
-fn generate_permutations(input: Vec<isize>) -> Vec<Vec<isize>> {
-    let mut permutations = vec![];
-    permute(&mut |o| permutations.push(o), input);
-    permutations
+/// Tries every order of the phase settings, spread over all cores, and
+/// returns the highest signal with the phases that produce it.
+pub fn max_signal(program: &Computer, phases: &[isize], feedback: bool) -> (isize, Vec<isize>) {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    par_max_by_key(phases, threads, |phases| amplify(program, phases, feedback)).expect("no phases")
 }
 
 pub fn part1(input: &str) -> String {
-    // create the computers
-    let amplifiers = vec![Computer::new(input); 5];
-    let mut max_output = 0;
-
-    for permutation in generate_permutations((0..5).collect()) {
-        // make sure to use fresh state
-        let mut amplifiers = amplifiers.clone();
-        let mut permutation = permutation;
-        // provide the sequence number as first input
-        for amplifier in &mut amplifiers {
-            amplifier.push_input(permutation.pop().unwrap());
-        }
-
-        // then run all the stuff, back to front
-        let mut current_output = 0;
-        while !amplifiers.is_empty() {
-            let mut amplifier = amplifiers.pop().unwrap();
-            amplifier.push_input(current_output);
-            current_output = amplifier.run_to_halt();
-        }
-
-        max_output = max_output.max(current_output);
-    }
-
-    max_output.to_string()
+    let program = Computer::new(input).expect("parsing program");
+    let phases: Vec<_> = (0..5).collect();
+    max_signal(&program, &phases, false).0.to_string()
 }
 
 pub fn part2(input: &str) -> String {
-    // create the computers
-    let amplifiers = vec![Computer::new(input); 5];
-    let mut max_output = 0;
-
-    for permutation in generate_permutations((5..10).collect()) {
-        // make sure to use fresh state
-        let mut amplifiers = amplifiers.clone();
-        let mut permutation = permutation;
-        // provide the sequence number as first input
-        for amplifier in &mut amplifiers {
-            amplifier.push_input(permutation.pop().unwrap());
-        }
-
-        // then run all the stuff, back to front
-        let mut current_output = 0;
-        let mut current_amplifier = 0;
-        loop {
-            let amplifier = &mut amplifiers[current_amplifier];
-            amplifier.push_input(current_output);
-            current_amplifier += 1;
-            current_output = match amplifier.run() {
-                RunResult::Completed(value) => {
-                    // if *all* the amplifiers are complete, stop the feedback loop
-                    if current_amplifier == amplifiers.len() {
-                        current_output = value;
-                        break;
-                    }
-                    value
-                }
-                RunResult::Waiting => amplifier.pop_output().expect("expected outout"),
-            };
-            // start from the front again
-            current_amplifier = current_amplifier % amplifiers.len();
-        }
-
-        max_output = max_output.max(current_output);
-    }
-
-    max_output.to_string()
+    let program = Computer::new(input).expect("parsing program");
+    let phases: Vec<_> = (5..10).collect();
+    max_signal(&program, &phases, true).0.to_string()
 }
@@ -80,6 +80,7 @@ pub fn part2(input: &str) -> String {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(&path)
         .expect("opening pbm");
 
@@ -3,8 +3,8 @@ use crate::computer::Computer;
 #[test]
 fn test_day9() {
     fn run_test(mem: &str) -> Vec<isize> {
-        let mut c = Computer::new(mem);
-        c.run_to_halt();
+        let mut c = Computer::new(mem).unwrap();
+        c.run_to_halt().unwrap();
         c.output().iter().cloned().collect()
     }
 
@@ -22,13 +22,13 @@ fn test_day9() {
 }
 
 pub fn part1(input: &str) -> String {
-    let mut c = Computer::new_with_input(input, 1);
-    let result = c.run_to_halt();
+    let mut c = Computer::new_with_input(input, 1).expect("parsing program");
+    let result = c.run_to_halt().expect("running program");
     result.to_string()
 }
 
 pub fn part2(input: &str) -> String {
-    let mut c = Computer::new_with_input(input, 2);
-    let result = c.run_to_halt();
+    let mut c = Computer::new_with_input(input, 2).expect("parsing program");
+    let result = c.run_to_halt().expect("running program");
     result.to_string()
 }
@@ -0,0 +1,605 @@
+use crate::computer::{Computer, Error, Instruction, Parameter, Step};
+use crate::disasm::Line;
+use crate::inspect;
+use crate::snapshot;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+#[test]
+fn test_debugger() {
+    // counts from 1 to 3, reading the step size from input
+    let program = "3,20,1,19,20,19,4,19,1007,19,3,21,1005,21,2,99,0,0,0,0,0,0";
+    let mut dbg = Debugger::new(Computer::new(program).unwrap());
+    assert_eq!(dbg.cont().unwrap(), Stop::Waiting);
+    dbg.computer_mut().push_input(1);
+
+    dbg.add_breakpoint(6);
+    assert_eq!(dbg.cont().unwrap(), Stop::Breakpoint(6));
+    assert!(dbg.computer().output().is_empty());
+    assert_eq!(dbg.step().unwrap(), Stop::Step);
+    assert_eq!(dbg.computer().output(), &[1]);
+    dbg.remove_breakpoint(6);
+
+    dbg.add_watchpoint(19);
+    assert_eq!(
+        dbg.cont().unwrap(),
+        Stop::Watchpoint {
+            addr: 19,
+            old: 1,
+            new: 2
+        }
+    );
+    assert_eq!(dbg.computer().pc(), 6);
+    dbg.remove_watchpoint(19);
+    assert_eq!(dbg.cont().unwrap(), Stop::Halted);
+    assert_eq!(dbg.computer().output(), &[1, 2, 3]);
+
+    let mut dbg = Debugger::new(Computer::new(program).unwrap());
+    let mut out = vec![];
+    dbg.repl(
+        "in 2\nb 15\nc\nx 19 3\nset 19 7\npc 6\ns\nout\nq\n".as_bytes(),
+        &mut out,
+    )
+    .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("breakpoint at 15\n   15: HLT\n"));
+    assert!(out.contains("   19: 4 2 0\n"));
+    assert!(out.contains("output: 2 4 7\n"));
+
+    // run to the end while recording, then go back in time
+    let mut dbg = Debugger::new(Computer::new(program).unwrap());
+    dbg.record(true);
+    dbg.computer_mut().push_input(1);
+    assert_eq!(dbg.cont().unwrap(), Stop::Halted);
+    assert_eq!(dbg.computer().output(), &[1, 2, 3]);
+    assert_eq!(dbg.run_back_to_write(19), Some(2));
+    assert_eq!(dbg.computer().peek(19), 2);
+    assert_eq!(dbg.computer().output(), &[1, 2]);
+    assert!(dbg.step_back());
+    assert_eq!(dbg.computer().pc(), 12);
+    assert_eq!(dbg.run_back_to_write(20), Some(0));
+    assert_eq!(dbg.computer().input(), &[1]);
+    assert!(!dbg.step_back());
+    assert_eq!(dbg.run_back_to_write(20), None);
+    assert_eq!(dbg.cont().unwrap(), Stop::Halted);
+    assert_eq!(dbg.computer().output(), &[1, 2, 3]);
+
+    // stepping back gives the budget back as well, and uncounts cycles
+    let mut dbg = Debugger::new(Computer::new_with_input(program, 1).unwrap());
+    dbg.record(true);
+    dbg.computer_mut().set_budget(Some(10));
+    dbg.step().unwrap();
+    dbg.step().unwrap();
+    assert_eq!(dbg.computer().budget(), Some(8));
+    assert_eq!(dbg.computer().cycles().total(), 2);
+    assert!(dbg.step_back());
+    assert!(dbg.step_back());
+    assert_eq!(dbg.computer().budget(), Some(10));
+    assert_eq!(dbg.computer().cycles().total(), 0);
+
+    let mut dbg = Debugger::new(Computer::new(program).unwrap());
+    let mut out = vec![];
+    dbg.repl(
+        "record\nin 1\nc\nlastwrite 19\nback\nq\n".as_bytes(),
+        &mut out,
+    )
+    .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("last write to 19\n    2: ADD  [19], [20], [19]\n"));
+    assert!(out.contains("\n   12: JNZ  [21], #2\n"));
+
+    // a trap opcode stops like a breakpoint does
+    use crate::isa::{Action, Extension, InstructionSet};
+    let mut set = InstructionSet::new();
+    set.register(42, Extension::new("TRAP", &[], |_| Ok(Action::Trap)));
+    let mut computer = Computer::new("104,1,42,104,2,99").unwrap();
+    computer.set_instruction_set(set);
+    let mut dbg = Debugger::new(computer);
+    let mut out = vec![];
+    dbg.repl("c\nc\nq\n".as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("trap at 2\n    3: OUT  #2\n"));
+    assert!(out.contains("halted\n"));
+}
+
+/// Why `Debugger::step` or `Debugger::cont` stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// A single step has finished.
+    Step,
+    Breakpoint(usize),
+    /// A watched memory cell was changed by the last instruction.
+    Watchpoint {
+        addr: usize,
+        old: isize,
+        new: isize,
+    },
+    Waiting,
+    Halted,
+    OutOfBudget,
+    /// An extension instruction at the address trapped.
+    Trap(usize),
+}
+
+/// What it takes to undo one executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Undo {
+    pc: usize,
+    /// The opcode of the instruction, which counted as a cycle.
+    opcode: isize,
+    relative_base: isize,
+    /// The instruction budget before the instruction used its part.
+    budget: Option<u64>,
+    /// The address the instruction wrote to, and its old value.
+    write: Option<(usize, isize)>,
+    /// The input the instruction took from the queue.
+    input: Option<isize>,
+    /// Whether the instruction added to the output queue.
+    output: bool,
+}
+
+pub struct Debugger {
+    computer: Computer,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    /// The undo log, while recording.
+    history: Option<Vec<Undo>>,
+}
+
+/// The address `inst` is going to write to, if it writes anywhere valid.
+fn write_address(computer: &Computer, inst: &Instruction) -> Option<usize> {
+    let dst = match inst {
+        Instruction::Arithmetic(.., dst) | Instruction::Relation(.., dst) => dst,
+        Instruction::Input(dst) => dst,
+        Instruction::Extension(custom) => {
+            let extension = computer.instruction_set().extension(custom.opcode)?;
+            &custom.parameters[extension.write_parameter()?]
+        }
+        _ => return None,
+    };
+    match dst {
+        Parameter::Position(addr) => Some(*addr),
+        Parameter::Relative(offset) => computer
+            .relative_base()
+            .checked_add(*offset)
+            .filter(|addr| *addr >= 0)
+            .map(|addr| addr as usize),
+        Parameter::Immediate(_) => None,
+    }
+}
+
+/// The instruction at `addr` the way the computer would decode it.
+fn line_at(computer: &Computer, addr: usize) -> Line {
+    let memory = computer.memory();
+    match Instruction::decode_with(memory, addr, computer.instruction_set()) {
+        Ok(inst) => Line::Instruction(addr, inst),
+        Err(_) => Line::Data(addr, memory.get(addr).copied().unwrap_or(0)),
+    }
+}
+
+const HELP: &str = "\
+s, step [n]         execute n instructions
+c, continue         run until a breakpoint, trap, watchpoint, input wait or halt
+b, break [addr]     set a breakpoint on pc, or list all breakpoints
+d, delete <addr>    remove a breakpoint
+w, watch [addr]     stop when memory at addr changes, or list all watchpoints
+unwatch <addr>      remove a watchpoint
+x, mem <addr> [n]   print n memory cells
+dump <addr> [n]     print n memory cells in columns, marking pc and rb
+set <addr> <value>  write to memory
+pc [addr]           print or change the pc
+rb [value]          print or change the relative base
+in [values...]      print the input queue, or push values to it
+out [clear]         print or clear the output queue
+l, list [addr] [n]  disassemble n instructions
+r, regs             print pc, relative base and queue sizes
+record [on|off]     keep an undo log of every executed instruction
+back [n]            undo n recorded instructions
+lastwrite <addr>    go back to the last recorded write to addr
+save <path>         write a snapshot of the computer to a file
+load <path>         restore the computer from a snapshot file
+q, quit             leave the debugger
+";
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Debugger {
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            history: None,
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn into_computer(self) -> Computer {
+        self.computer
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn add_watchpoint(&mut self, addr: usize) {
+        self.watchpoints.insert(addr);
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr)
+    }
+
+    /// Starts or stops recording. While recording, every executed
+    /// instruction is logged, so that it can be undone with `step_back`.
+    /// Stopping drops the log.
+    pub fn record(&mut self, on: bool) {
+        if on != self.history.is_some() {
+            self.history = if on { Some(vec![]) } else { None };
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// How many instructions can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Forgets the recorded history, because the state was changed by hand
+    /// and can no longer be undone step by step.
+    fn truncate_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    /// Executes a single instruction, ignoring breakpoints.
+    pub fn step(&mut self) -> Result<Stop, Error> {
+        let watched: Vec<_> = self
+            .watchpoints
+            .iter()
+            .map(|addr| (*addr, self.computer.peek(*addr)))
+            .collect();
+        // decoding here is only needed for the undo log
+        let pc = self.computer.pc();
+        let inst = if self.is_recording() {
+            let set = self.computer.instruction_set();
+            Instruction::decode_with(self.computer.memory(), pc, set).ok()
+        } else {
+            None
+        };
+        let write = inst
+            .as_ref()
+            .and_then(|inst| write_address(&self.computer, inst))
+            .map(|addr| (addr, self.computer.peek(addr)));
+        let relative_base = self.computer.relative_base();
+        let budget = self.computer.budget();
+
+        let trapped = match self.computer.step()? {
+            Step::Waiting => return Ok(Stop::Waiting),
+            Step::Halted => return Ok(Stop::Halted),
+            Step::OutOfBudget => return Ok(Stop::OutOfBudget),
+            Step::Trapped => true,
+            Step::Executed => false,
+        };
+        if let (Some(history), Some(inst)) = (&mut self.history, inst) {
+            history.push(Undo {
+                pc,
+                opcode: inst.opcode(),
+                relative_base,
+                budget,
+                write,
+                input: match (&inst, write) {
+                    (Instruction::Input(_), Some((addr, _))) => Some(self.computer.peek(addr)),
+                    _ => None,
+                },
+                output: matches!(inst, Instruction::Output(_)),
+            });
+        }
+        for (addr, old) in watched {
+            let new = self.computer.peek(addr);
+            if new != old {
+                return Ok(Stop::Watchpoint { addr, old, new });
+            }
+        }
+        if trapped {
+            return Ok(Stop::Trap(pc));
+        }
+        Ok(Stop::Step)
+    }
+
+    /// Runs until something interesting happens. This always executes at
+    /// least one instruction, so continuing from a breakpoint moves on.
+    pub fn cont(&mut self) -> Result<Stop, Error> {
+        loop {
+            match self.step()? {
+                Stop::Step if self.breakpoints.contains(&self.computer.pc()) => {
+                    return Ok(Stop::Breakpoint(self.computer.pc()))
+                }
+                Stop::Step => {}
+                stop => return Ok(stop),
+            }
+        }
+    }
+
+    /// Undoes the last recorded instruction, cycle counts included.
+    /// Returns `false` if there is nothing left to undo.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.as_mut().and_then(Vec::pop) {
+            Some(undo) => undo,
+            None => return false,
+        };
+        if let Some((addr, old)) = undo.write {
+            self.computer.poke(addr, old);
+        }
+        if let Some(value) = undo.input {
+            self.computer.input_mut().push_front(value);
+        }
+        if undo.output {
+            self.computer.output_mut().pop_back();
+        }
+        self.computer.set_pc(undo.pc);
+        self.computer.set_relative_base(undo.relative_base);
+        self.computer.set_budget(undo.budget);
+        self.computer.cycles_mut().uncount(undo.opcode);
+        true
+    }
+
+    /// Steps back until right before the last recorded write to `addr`, and
+    /// returns the pc of the instruction that wrote it. If there is no such
+    /// write, this goes back to the start of the recording and returns
+    /// `None`.
+    pub fn run_back_to_write(&mut self, addr: usize) -> Option<usize> {
+        loop {
+            let wrote = match self.history.as_ref().and_then(|h| h.last()) {
+                Some(undo) => undo.write.is_some_and(|(a, _)| a == addr),
+                None => return None,
+            };
+            self.step_back();
+            if wrote {
+                return Some(self.computer.pc());
+            }
+        }
+    }
+
+    fn print_stop<W: Write>(&self, output: &mut W, stop: Stop) -> io::Result<()> {
+        match stop {
+            Stop::Step => {}
+            Stop::Breakpoint(addr) => writeln!(output, "breakpoint at {}", addr)?,
+            Stop::Watchpoint { addr, old, new } => {
+                writeln!(output, "watchpoint at {}: {} -> {}", addr, old, new)?
+            }
+            Stop::Waiting => writeln!(output, "waiting for input")?,
+            Stop::Halted => writeln!(output, "halted")?,
+            Stop::OutOfBudget => writeln!(output, "instruction budget exhausted")?,
+            Stop::Trap(addr) => writeln!(output, "trap at {}", addr)?,
+        }
+        let pc = self.computer.pc();
+        writeln!(output, "{}", line_at(&self.computer, pc))
+    }
+
+    fn print_queue<W: Write>(output: &mut W, name: &str, queue: &[isize]) -> io::Result<()> {
+        write!(output, "{}:", name)?;
+        for value in queue {
+            write!(output, " {}", value)?;
+        }
+        writeln!(output)
+    }
+
+    fn snapshot_command<W: Write>(
+        &mut self,
+        output: &mut W,
+        cmd: &str,
+        path: &str,
+    ) -> io::Result<()> {
+        let result = if cmd == "save" {
+            std::fs::File::create(path)
+                .and_then(|file| snapshot::save(&self.computer, io::BufWriter::new(file)))
+                .map_err(snapshot::Error::Io)
+        } else {
+            std::fs::File::open(path)
+                .map_err(snapshot::Error::Io)
+                .and_then(|file| snapshot::restore(&mut self.computer, io::BufReader::new(file)))
+        };
+        match result {
+            Ok(()) if cmd == "load" => {
+                self.truncate_history();
+                self.print_stop(output, Stop::Step)
+            }
+            Ok(()) => Ok(()),
+            Err(err) => writeln!(output, "{}: {}", path, err),
+        }
+    }
+
+    /// Runs one REPL command, returning `false` when the user wants to quit.
+    fn command<W: Write>(&mut self, output: &mut W, line: &str) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => return Ok(true),
+        };
+        if cmd == "save" || cmd == "load" {
+            let path = words.collect::<Vec<_>>().join(" ");
+            return self.snapshot_command(output, cmd, &path).map(|_| true);
+        }
+        let args: Result<Vec<isize>, _> = words.map(str::parse).collect();
+        let args = match args {
+            Ok(args) => args,
+            Err(_) if cmd == "out" => vec![],
+            Err(_) => {
+                writeln!(output, "invalid number")?;
+                return Ok(true);
+            }
+        };
+        // all the addresses need to be positive
+        let addr = |i: usize| args.get(i).filter(|a| **a >= 0).map(|a| *a as usize);
+
+        let result = match (cmd, args.len()) {
+            ("q", _) | ("quit", _) => return Ok(false),
+            ("h", _) | ("help", _) => write!(output, "{}", HELP).map(|_| None),
+            ("s", _) | ("step", _) => {
+                let mut stop = Ok(Stop::Step);
+                for _ in 0..args.first().copied().unwrap_or(1) {
+                    stop = self.step();
+                    if stop != Ok(Stop::Step) {
+                        break;
+                    }
+                }
+                stop.map(Some)
+                    .or_else(|err| writeln!(output, "error: {}", err).map(|_| None))
+            }
+            ("c", _) | ("continue", _) => self
+                .cont()
+                .map(Some)
+                .or_else(|err| writeln!(output, "error: {}", err).map(|_| None)),
+            ("b", 0) | ("break", 0) => {
+                let bps: Vec<_> = self.breakpoints.iter().map(|a| *a as isize).collect();
+                Self::print_queue(output, "breakpoints", &bps).map(|_| None)
+            }
+            ("b", 1) | ("break", 1) if addr(0).is_some() => {
+                self.add_breakpoint(addr(0).unwrap());
+                Ok(None)
+            }
+            ("d", 1) | ("delete", 1) if addr(0).is_some() => {
+                self.remove_breakpoint(addr(0).unwrap());
+                Ok(None)
+            }
+            ("w", 0) | ("watch", 0) => {
+                let wps: Vec<_> = self.watchpoints.iter().map(|a| *a as isize).collect();
+                Self::print_queue(output, "watchpoints", &wps).map(|_| None)
+            }
+            ("w", 1) | ("watch", 1) if addr(0).is_some() => {
+                self.add_watchpoint(addr(0).unwrap());
+                Ok(None)
+            }
+            ("unwatch", 1) if addr(0).is_some() => {
+                self.remove_watchpoint(addr(0).unwrap());
+                Ok(None)
+            }
+            ("x", 1) | ("x", 2) | ("mem", 1) | ("mem", 2) if addr(0).is_some() => {
+                let start = addr(0).unwrap();
+                let len = addr(1).unwrap_or(1);
+                write!(output, "{:>5}:", start)?;
+                for a in start..start + len {
+                    write!(output, " {}", self.computer.peek(a))?;
+                }
+                writeln!(output).map(|_| None)
+            }
+            ("dump", 1) | ("dump", 2) if addr(0).is_some() => {
+                let start = addr(0).unwrap();
+                let end = start + addr(1).unwrap_or(32);
+                write!(
+                    output,
+                    "{}",
+                    inspect::dump(&self.computer, start..end).to_text()
+                )
+                .map(|_| None)
+            }
+            ("set", 2) if addr(0).is_some() => {
+                self.computer.poke(addr(0).unwrap(), args[1]);
+                self.truncate_history();
+                Ok(None)
+            }
+            ("pc", 0) => writeln!(output, "pc: {}", self.computer.pc()).map(|_| None),
+            ("pc", 1) if addr(0).is_some() => {
+                self.computer.set_pc(addr(0).unwrap());
+                self.truncate_history();
+                Ok(None)
+            }
+            ("rb", 0) => writeln!(output, "rb: {}", self.computer.relative_base()).map(|_| None),
+            ("rb", 1) => {
+                self.computer.set_relative_base(args[0]);
+                self.truncate_history();
+                Ok(None)
+            }
+            ("in", 0) => {
+                let input: Vec<_> = self.computer.input().iter().copied().collect();
+                Self::print_queue(output, "input", &input).map(|_| None)
+            }
+            ("in", _) => {
+                self.computer.input_mut().extend(args.iter());
+                Ok(None)
+            }
+            ("out", _) if line.split_whitespace().nth(1) == Some("clear") => {
+                self.computer.output_mut().clear();
+                Ok(None)
+            }
+            ("out", _) => {
+                let out: Vec<_> = self.computer.output().iter().copied().collect();
+                Self::print_queue(output, "output", &out).map(|_| None)
+            }
+            ("l", _) | ("list", _) if args.len() <= 2 => {
+                let mut a = addr(0).unwrap_or_else(|| self.computer.pc());
+                for _ in 0..addr(1).unwrap_or(10) {
+                    let line = line_at(&self.computer, a);
+                    let marker = if a == self.computer.pc() { ">" } else { " " };
+                    writeln!(output, "{}{}", marker, line)?;
+                    a += line.width();
+                }
+                Ok(None)
+            }
+            ("record", _) => {
+                let words: Vec<_> = line.split_whitespace().skip(1).collect();
+                match words.as_slice() {
+                    [] | ["on"] => self.record(true),
+                    ["off"] => self.record(false),
+                    _ => writeln!(output, "usage: record [on|off]")?,
+                }
+                Ok(None)
+            }
+            ("back", _) if args.len() <= 1 => {
+                let n = addr(0).unwrap_or(1);
+                let undone = (0..n).take_while(|_| self.step_back()).count();
+                if undone < n {
+                    writeln!(output, "reached the start of the recording")?;
+                }
+                Ok(Some(Stop::Step))
+            }
+            ("lastwrite", 1) if addr(0).is_some() => {
+                match self.run_back_to_write(addr(0).unwrap()) {
+                    Some(_) => writeln!(output, "last write to {}", args[0])?,
+                    None => writeln!(output, "no recorded write to {}", args[0])?,
+                }
+                Ok(Some(Stop::Step))
+            }
+            ("r", 0) | ("regs", 0) => writeln!(
+                output,
+                "pc: {}, rb: {}, input: {}, output: {}",
+                self.computer.pc(),
+                self.computer.relative_base(),
+                self.computer.input().len(),
+                self.computer.output().len()
+            )
+            .map(|_| None),
+            _ => writeln!(output, "unknown command, try `help`").map(|_| None),
+        }?;
+        if let Some(stop) = result {
+            self.print_stop(output, stop)?;
+        }
+        Ok(true)
+    }
+
+    /// Reads commands line by line from `input` until `quit` or EOF.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        self.print_stop(&mut output, Stop::Step)?;
+        for line in input.lines() {
+            if !self.command(&mut output, &line?)? {
+                break;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
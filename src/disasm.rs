@@ -0,0 +1,115 @@
+use crate::cell::Cell;
+use crate::computer::{Instruction, Parameter};
+use std::fmt;
+
+#[test]
+fn test_disasm() {
+    let mem = [1101, 5, -3, 9, 204, -1, 1006, 9, 0, 99, 42, 10099];
+    assert_eq!(
+        listing(&mem),
+        "    0: ADD  #5, #-3, [9]
+    4: OUT  rb-1
+    6: JZ   [9], #0
+    9: HLT
+   10: db   42
+   11: db   10099
+"
+    );
+
+    // an instruction that runs off the end of memory is data as well
+    assert_eq!(listing(&[4]), "    0: db   4\n");
+}
+
+impl<V: Cell> fmt::Display for Parameter<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Position(addr) => write!(f, "[{}]", addr),
+            Parameter::Immediate(value) => write!(f, "#{}", value),
+            Parameter::Relative(offset) if offset.is_negative() => write!(f, "rb{}", offset),
+            Parameter::Relative(offset) => write!(f, "rb+{}", offset),
+        }
+    }
+}
+
+impl<V: Cell> fmt::Display for Instruction<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self.parameters();
+        if params.is_empty() {
+            return write!(f, "{}", self.mnemonic());
+        }
+        write!(f, "{:<4} ", self.mnemonic())?;
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line of a disassembly listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction(usize, Instruction),
+    /// A word that does not decode to an instruction.
+    Data(usize, isize),
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction(addr, _) | Line::Data(addr, _) => *addr,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        match self {
+            Line::Instruction(_, inst) => inst.width(),
+            Line::Data(..) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Instruction(addr, inst) => write!(f, "{:>5}: {}", addr, inst),
+            Line::Data(addr, value) => write!(f, "{:>5}: {:<4} {}", addr, "db", value),
+        }
+    }
+}
+
+/// Decodes the instruction at `addr`, but only if it fits into memory and
+/// encodes back to the exact same words. Anything else, like superfluous
+/// parameter mode digits, is treated as data so the listing stays faithful.
+pub fn decode_at(memory: &[isize], addr: usize) -> Option<Instruction> {
+    let inst = Instruction::decode(memory, addr).ok()?;
+    let words = memory.get(addr..addr + inst.width())?;
+    if inst.encode() != words {
+        return None;
+    }
+    Some(inst)
+}
+
+/// Does a linear sweep over `memory`, decoding one instruction after another.
+pub fn disassemble(memory: &[isize]) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut addr = 0;
+    while addr < memory.len() {
+        let line = match decode_at(memory, addr) {
+            Some(inst) => Line::Instruction(addr, inst),
+            None => Line::Data(addr, memory[addr]),
+        };
+        addr += line.width();
+        lines.push(line);
+    }
+    lines
+}
+
+pub fn listing(memory: &[isize]) -> String {
+    disassemble(memory)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
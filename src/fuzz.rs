@@ -0,0 +1,441 @@
+use crate::computer::{Computer, Error, ErrorKind, JumpOp, Step};
+use std::fmt;
+
+#[test]
+fn test_fuzz() {
+    assert_eq!(fuzz(1, 300).map_err(|c| c.to_string()), Ok(()));
+
+    let outcome = reference(&[3, 9, 1002, 9, 2, 9, 4, 9, 99, 0], &[21], 100, 100);
+    assert_eq!(outcome.exit, Exit::Halted);
+    assert_eq!(outcome.output, [42]);
+    let program = [1106, 1, -1, 104, 5, 99];
+    assert_eq!(reference(&program, &[], 100, 100).output, [5]);
+    assert!(check(&program, &[]).is_none());
+    let outcome = reference(&[109, -1, 204, 0], &[], 100, 100);
+    assert_eq!(
+        outcome.exit,
+        Exit::Failed(Error {
+            pc: 2,
+            word: 204,
+            kind: ErrorKind::NegativeAddress(-1)
+        })
+    );
+
+    // shrinks a program down to the part that still outputs a 7
+    let program = [1101, 3, 4, 20, 3, 21, 104, 7, 1, 20, 21, 22, 99];
+    let outputs_7 = |p: &[isize]| reference(p, &[], 100, 100).output.contains(&7);
+    assert_eq!(minimize(&program, outputs_7), [104, 7]);
+}
+
+/// How many instructions a generated program may execute.
+const BUDGET: u64 = 2_000;
+
+/// How many memory cells a generated program may use.
+const MEMORY_LIMIT: usize = 4_096;
+
+/// A small xorshift PRNG, so that every seed always generates the same
+/// programs.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // the state must never be zero
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A number in `range`, which includes both ends.
+    pub fn between(&mut self, low: isize, high: isize) -> isize {
+        low + self.below((high - low + 1) as usize) as isize
+    }
+
+    /// `true` with a chance of `percent` in 100.
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// Generates a program of roughly `len` words, which decodes everywhere
+/// execution starts out, but may do anything from there: loop, modify
+/// itself, overflow, or use negative addresses. Now and then it contains
+/// an invalid parameter mode or a write to an immediate parameter.
+pub fn generate(rng: &mut Rng, len: usize) -> Vec<isize> {
+    let mut program = vec![];
+    let mut starts = vec![];
+    while program.len() + 4 < len {
+        starts.push(program.len());
+        let opcode = [1, 2, 3, 4, 5, 6, 7, 8, 9][rng.below(9)];
+        let (reads, writes) = match opcode {
+            1 | 2 | 7 | 8 => (2, 1),
+            3 => (0, 1),
+            5 | 6 => (2, 0),
+            _ => (1, 0),
+        };
+        let mut modes = 0;
+        let mut params = vec![];
+        for n in 0..reads + writes {
+            let write = n >= reads;
+            let mode = match rng.below(100) {
+                0 => 3,
+                1..=2 if write => 1,
+                _ if write => [0, 2][rng.below(2)],
+                x if x < 45 => 0,
+                x if x < 80 => 1,
+                _ => 2,
+            };
+            let value = match mode {
+                0 => rng.below(len + 8) as isize,
+                1 if opcode == 9 => rng.between(-2, 10),
+                // jump targets are mostly instructions
+                1 if n == 1 && (opcode == 5 || opcode == 6) && rng.chance(80) => {
+                    starts[rng.below(starts.len())] as isize
+                }
+                1 if rng.chance(5) => isize::MAX - rng.between(0, 3),
+                1 if rng.chance(5) => isize::MIN + rng.between(0, 3),
+                1 => rng.between(-10, len as isize),
+                2 => rng.between(-2, 8),
+                _ => rng.between(0, 9),
+            };
+            modes += mode * 10isize.pow(n as u32);
+            params.push(value);
+        }
+        program.push(modes * 100 + opcode);
+        program.extend(params);
+    }
+    program.push(99);
+    for _ in 0..rng.below(4) {
+        program.push(rng.between(-5, 20));
+    }
+    program
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Halted,
+    Waiting,
+    OutOfBudget,
+    Failed(Error),
+}
+
+/// Everything that can be observed after a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub exit: Exit,
+    pub pc: usize,
+    pub relative_base: isize,
+    pub output: Vec<isize>,
+    /// The memory, without any trailing zeros.
+    pub memory: Vec<isize>,
+}
+
+fn trim_zeros(mut memory: Vec<isize>) -> Vec<isize> {
+    while memory.last() == Some(&0) {
+        memory.pop();
+    }
+    memory
+}
+
+/// The state of the `reference` interpreter.
+struct Reference<'a> {
+    memory: Vec<isize>,
+    memory_limit: usize,
+    pc: usize,
+    rb: isize,
+    input: std::slice::Iter<'a, isize>,
+    output: Vec<isize>,
+}
+
+impl Reference<'_> {
+    fn load(&self, addr: usize) -> isize {
+        self.memory.get(addr).copied().unwrap_or(0)
+    }
+
+    /// The address a parameter, given as its mode and raw value, refers to.
+    fn address(&self, (mode, value): (isize, isize)) -> Result<usize, ErrorKind> {
+        match mode {
+            0 => Ok(value as usize),
+            1 => Err(ErrorKind::WriteToImmediate),
+            _ => match self.rb.checked_add(value) {
+                None => Err(ErrorKind::Overflow),
+                Some(addr) if addr < 0 => Err(ErrorKind::NegativeAddress(addr)),
+                Some(addr) => Ok(addr as usize),
+            },
+        }
+    }
+
+    fn read(&self, param: (isize, isize)) -> Result<isize, ErrorKind> {
+        match param.0 {
+            1 => Ok(param.1),
+            _ => Ok(self.load(self.address(param)?)),
+        }
+    }
+
+    fn write(&mut self, param: (isize, isize), value: isize) -> Result<(), ErrorKind> {
+        let addr = self.address(param)?;
+        if addr >= self.memory.len() {
+            if addr + 1 > self.memory_limit {
+                return Err(ErrorKind::MemoryLimit(addr));
+            }
+            self.memory.resize(addr + 1, 0);
+        }
+        self.memory[addr] = value;
+        Ok(())
+    }
+
+    /// Executes one instruction, and returns how the run ended if it did.
+    fn step(&mut self) -> Result<Option<Exit>, ErrorKind> {
+        let word = self.load(self.pc);
+        let opcode = word % 100;
+        let count = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            3 | 4 | 9 => 1,
+            5 | 6 => 2,
+            99 => return Ok(Some(Exit::Halted)),
+            _ => return Err(ErrorKind::InvalidOpcode(opcode)),
+        };
+        let mut params = vec![];
+        let mut divisor = 100;
+        for n in 1..=count {
+            let (mode, value) = (word / divisor % 10, self.load(self.pc + n));
+            match mode {
+                0 if value < 0 => return Err(ErrorKind::NegativeAddress(value)),
+                0..=2 => params.push((mode, value)),
+                _ => return Err(ErrorKind::InvalidParameterMode(mode)),
+            }
+            divisor *= 10;
+        }
+
+        let mut next = self.pc + count + 1;
+        match opcode {
+            1 | 2 | 7 | 8 => {
+                let a = self.read(params[0])?;
+                let b = self.read(params[1])?;
+                let value = match opcode {
+                    1 => a.checked_add(b).ok_or(ErrorKind::Overflow)?,
+                    2 => a.checked_mul(b).ok_or(ErrorKind::Overflow)?,
+                    7 => (a < b) as isize,
+                    _ => (a == b) as isize,
+                };
+                self.write(params[2], value)?;
+            }
+            3 => match self.input.next() {
+                Some(value) => self.write(params[0], *value)?,
+                None => return Ok(Some(Exit::Waiting)),
+            },
+            4 => {
+                let value = self.read(params[0])?;
+                self.output.push(value);
+            }
+            5 | 6 => {
+                let value = self.read(params[0])?;
+                let target = self.read(params[1])?;
+                let op = if opcode == 5 {
+                    JumpOp::IfNotZero
+                } else {
+                    JumpOp::IfZero
+                };
+                if op.taken(&value) {
+                    if target < 0 {
+                        return Err(ErrorKind::NegativeAddress(target));
+                    }
+                    next = target as usize;
+                }
+            }
+            _ => {
+                let value = self.read(params[0])?;
+                self.rb = self.rb.checked_add(value).ok_or(ErrorKind::Overflow)?;
+            }
+        }
+        self.pc = next;
+        Ok(None)
+    }
+}
+
+/// A straightforward Intcode interpreter that shares no code with
+/// `Computer`, to check it against. This stops after `budget` instructions
+/// and refuses to grow memory beyond `memory_limit` cells, like a
+/// `Computer` with the same settings.
+pub fn reference(program: &[isize], input: &[isize], budget: u64, memory_limit: usize) -> Outcome {
+    let mut state = Reference {
+        memory: program.to_vec(),
+        memory_limit,
+        pc: 0,
+        rb: 0,
+        input: input.iter(),
+        output: vec![],
+    };
+    let mut executed = 0;
+    let exit = loop {
+        if executed == budget {
+            break Exit::OutOfBudget;
+        }
+        match state.step() {
+            Ok(Some(exit)) => break exit,
+            Ok(None) => executed += 1,
+            Err(kind) => {
+                break Exit::Failed(Error {
+                    pc: state.pc,
+                    word: state.load(state.pc),
+                    kind,
+                })
+            }
+        }
+    };
+    Outcome {
+        exit,
+        pc: state.pc,
+        relative_base: state.rb,
+        output: state.output,
+        memory: trim_zeros(state.memory),
+    }
+}
+
+/// Runs a program on a `Computer` with the same settings as `reference`.
+fn run_computer(program: &[isize], input: &[isize], budget: u64, memory_limit: usize) -> Outcome {
+    let mut computer: Computer = Computer::from_memory(program.to_vec());
+    computer.set_budget(Some(budget));
+    computer.set_memory_limit(Some(memory_limit));
+    input.iter().for_each(|value| computer.push_input(*value));
+    let exit = match computer.resume() {
+        Ok(Step::Halted) => Exit::Halted,
+        Ok(Step::Waiting) => Exit::Waiting,
+        Ok(_) => Exit::OutOfBudget,
+        Err(err) => Exit::Failed(err),
+    };
+    Outcome {
+        exit,
+        pc: computer.pc(),
+        relative_base: computer.relative_base(),
+        output: computer.output().iter().copied().collect(),
+        memory: trim_zeros(computer.memory().clone()),
+    }
+}
+
+/// A program on which `Computer` and `reference` disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub program: Vec<isize>,
+    pub input: Vec<isize>,
+    pub expected: Outcome,
+    pub actual: Outcome,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.exit {
+            Exit::Halted => write!(f, "halted")?,
+            Exit::Waiting => write!(f, "waiting for input")?,
+            Exit::OutOfBudget => write!(f, "out of budget")?,
+            Exit::Failed(err) => write!(f, "failed: {}", err)?,
+        }
+        write!(
+            f,
+            ", pc {}, rb {}, output {:?}",
+            self.pc, self.relative_base, self.output
+        )
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |values: &[isize]| {
+            let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+            values.join(",")
+        };
+        writeln!(f, "program: {}", list(&self.program))?;
+        writeln!(f, "input: {}", list(&self.input))?;
+        writeln!(f, "reference: {}", self.expected)?;
+        writeln!(f, "computer:  {}", self.actual)?;
+        let (expected, actual) = (&self.expected.memory, &self.actual.memory);
+        let len = expected.len().max(actual.len());
+        let load = |memory: &[isize], addr| memory.get(addr).copied().unwrap_or(0);
+        if let Some(addr) = (0..len).find(|a| load(expected, *a) != load(actual, *a)) {
+            writeln!(
+                f,
+                "memory differs at {}: {} vs {}",
+                addr,
+                load(expected, addr),
+                load(actual, addr)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a program on both interpreters, and returns the differences.
+pub fn check(program: &[isize], input: &[isize]) -> Option<Counterexample> {
+    let expected = reference(program, input, BUDGET, MEMORY_LIMIT);
+    let actual = run_computer(program, input, BUDGET, MEMORY_LIMIT);
+    if expected == actual {
+        return None;
+    }
+    Some(Counterexample {
+        program: program.to_vec(),
+        input: input.to_vec(),
+        expected,
+        actual,
+    })
+}
+
+/// Makes a program as short and simple as possible while it keeps failing:
+/// removes runs of words, and zeroes single ones, for as long as that
+/// changes anything.
+pub fn minimize<F: Fn(&[isize]) -> bool>(program: &[isize], fails: F) -> Vec<isize> {
+    let mut program = program.to_vec();
+    loop {
+        let before = program.clone();
+        for width in (1..=4).rev() {
+            let mut start = 0;
+            while start + width <= program.len() {
+                let mut shorter = program.clone();
+                shorter.drain(start..start + width);
+                if fails(&shorter) {
+                    program = shorter;
+                } else {
+                    start += 1;
+                }
+            }
+        }
+        for addr in 0..program.len() {
+            if program[addr] != 0 {
+                let mut simpler = program.clone();
+                simpler[addr] = 0;
+                if fails(&simpler) {
+                    program = simpler;
+                }
+            }
+        }
+        if program == before {
+            return program;
+        }
+    }
+}
+
+/// Checks `runs` generated programs with random inputs against the
+/// reference, and returns the first disagreement, minimized.
+pub fn fuzz(seed: u64, runs: usize) -> Result<(), Box<Counterexample>> {
+    let mut rng = Rng::new(seed);
+    for _ in 0..runs {
+        let len = 8 + rng.below(56);
+        let program = generate(&mut rng, len);
+        let input: Vec<isize> = (0..rng.below(5)).map(|_| rng.between(-3, 30)).collect();
+        if check(&program, &input).is_some() {
+            let program = minimize(&program, |p| check(p, &input).is_some());
+            return Err(Box::new(check(&program, &input).unwrap()));
+        }
+    }
+    Ok(())
+}
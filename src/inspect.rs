@@ -0,0 +1,247 @@
+use crate::cell::Cell;
+use crate::computer::Computer;
+use crate::memory::Memory;
+use std::fmt::Write;
+use std::ops::Range;
+
+#[test]
+fn test_inspect() {
+    use crate::memory::PagedMemory;
+
+    let mut c = Computer::new("1101,100,-1,4,0,99").unwrap();
+    c.set_pc(4);
+    c.set_relative_base(2);
+    let view = dump(&c, 1..6).columns(4);
+    assert_eq!(
+        view.to_text(),
+        "pc 4, rb 2
+         +0   +1   +2   +3
+    0:       100  *-1    4
+    4:   >0   99
+"
+    );
+    assert_eq!(
+        view.to_json(),
+        r#"{"start":1,"pc":4,"relative_base":2,"cells":[100,-1,4,0,99]}"#
+    );
+
+    c.set_pc(0);
+    let before = c.clone();
+    c.resume().unwrap();
+    let changes = diff(&before, &c);
+    assert_eq!(changes.to_text(), "pc: 0 -> 4\n    4: 0 -> 99\n");
+    assert_eq!(
+        changes.to_json(),
+        r#"{"pc":[0,4],"relative_base":null,"cells":[{"addr":4,"old":0,"new":99}]}"#
+    );
+    assert!(diff_range(&before, &c, 0..4).cells.is_empty());
+
+    // writes anywhere are found, also far beyond the program
+    let mut c = Computer::<isize, PagedMemory<isize>>::parse("1101,7,0,5000,99").unwrap();
+    let before = c.clone();
+    c.resume().unwrap();
+    assert_eq!(diff(&before, &c).to_text(), "pc: 0 -> 4\n 5000: 0 -> 7\n");
+    assert_eq!(diff(&c, &before).to_text(), "pc: 4 -> 0\n 5000: 7 -> 0\n");
+}
+
+/// How many cells `dump` puts in a row, unless configured otherwise.
+const COLUMNS: usize = 8;
+
+/// A range of memory together with the registers, to be printed as text in
+/// aligned columns, or as JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dump<V = isize> {
+    start: usize,
+    cells: Vec<V>,
+    pc: usize,
+    relative_base: V,
+    columns: usize,
+}
+
+/// Takes the memory cells in `range` from `computer`.
+pub fn dump<V: Cell, M: Memory<V>>(computer: &Computer<V, M>, range: Range<usize>) -> Dump<V> {
+    Dump {
+        start: range.start,
+        cells: range.map(|addr| computer.peek(addr)).collect(),
+        pc: computer.pc(),
+        relative_base: computer.relative_base(),
+        columns: COLUMNS,
+    }
+}
+
+impl<V: Cell> Dump<V> {
+    pub fn columns(mut self, columns: usize) -> Dump<V> {
+        self.columns = columns.max(1);
+        self
+    }
+
+    /// The rows start at multiples of the column count, each with its
+    /// address. The cell at the pc is marked with `>`, the one at the
+    /// relative base with `*`, and a cell that is both with `@`.
+    pub fn to_text(&self) -> String {
+        let rb = self
+            .relative_base
+            .to_isize()
+            .filter(|rb| *rb >= 0)
+            .map(|rb| rb as usize);
+        let cells: Vec<String> = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let addr = self.start + i;
+                let marker = match (addr == self.pc, Some(addr) == rb) {
+                    (true, true) => "@",
+                    (true, false) => ">",
+                    (false, true) => "*",
+                    (false, false) => "",
+                };
+                format!("{}{}", marker, value)
+            })
+            .collect();
+        // room for a marker in every column
+        let width = self.cells.iter().map(|v| v.to_string().len() + 1).max();
+        let width = width.unwrap_or(0).max(3);
+
+        let mut out = format!("pc {}, rb {}\n", self.pc, self.relative_base);
+        out.push_str("      ");
+        for column in 0..self.columns {
+            write!(out, " {:>w$}", format!("+{}", column), w = width).unwrap();
+        }
+        out.push('\n');
+        let first = self.start - self.start % self.columns;
+        let end = self.start + self.cells.len();
+        for row in (first..end).step_by(self.columns) {
+            let mut line = format!("{:>5}:", row);
+            for addr in row..row + self.columns {
+                let cell = match addr.checked_sub(self.start) {
+                    Some(i) if i < cells.len() => &cells[i],
+                    _ => "",
+                };
+                write!(line, " {:>w$}", cell, w = width).unwrap();
+            }
+            writeln!(out, "{}", line.trim_end()).unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let cells: Vec<String> = self.cells.iter().map(|v| v.to_string()).collect();
+        format!(
+            r#"{{"start":{},"pc":{},"relative_base":{},"cells":[{}]}}"#,
+            self.start,
+            self.pc,
+            self.relative_base,
+            cells.join(",")
+        )
+    }
+}
+
+/// A memory cell that is different in two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change<V = isize> {
+    pub addr: usize,
+    pub old: V,
+    pub new: V,
+}
+
+/// What changed between two snapshots of a computer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff<V = isize> {
+    pub pc: Option<(usize, usize)>,
+    pub relative_base: Option<(V, V)>,
+    pub cells: Vec<Change<V>>,
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+    if old != new {
+        Some((old, new))
+    } else {
+        None
+    }
+}
+
+/// Compares the registers and all memory cells of two computers, like a
+/// clone taken before `run` and the computer after.
+pub fn diff<V: Cell, M: Memory<V>>(before: &Computer<V, M>, after: &Computer<V, M>) -> Diff<V> {
+    // cells that neither computer allocated are zero in both
+    let mut ranges = before.memory().allocated_ranges();
+    ranges.extend(after.memory().allocated_ranges());
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = vec![];
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    let mut diff = diff_range(before, after, 0..0);
+    for range in merged {
+        diff.cells.extend(changes(before, after, range));
+    }
+    diff
+}
+
+/// Like `diff`, but only compares the memory cells in `range`.
+pub fn diff_range<V: Cell, M: Memory<V>>(
+    before: &Computer<V, M>,
+    after: &Computer<V, M>,
+    range: Range<usize>,
+) -> Diff<V> {
+    Diff {
+        pc: changed(before.pc(), after.pc()),
+        relative_base: changed(before.relative_base(), after.relative_base()),
+        cells: changes(before, after, range).collect(),
+    }
+}
+
+fn changes<'a, V: Cell, M: Memory<V>>(
+    before: &'a Computer<V, M>,
+    after: &'a Computer<V, M>,
+    range: Range<usize>,
+) -> impl Iterator<Item = Change<V>> + 'a {
+    range.filter_map(move |addr| {
+        let (old, new) = (before.peek(addr), after.peek(addr));
+        changed(old, new).map(|(old, new)| Change { addr, old, new })
+    })
+}
+
+impl<V: Cell> Diff<V> {
+    pub fn is_empty(&self) -> bool {
+        self.pc.is_none() && self.relative_base.is_none() && self.cells.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if let Some((old, new)) = &self.pc {
+            writeln!(out, "pc: {} -> {}", old, new).unwrap();
+        }
+        if let Some((old, new)) = &self.relative_base {
+            writeln!(out, "rb: {} -> {}", old, new).unwrap();
+        }
+        for change in &self.cells {
+            writeln!(out, "{:>5}: {} -> {}", change.addr, change.old, change.new).unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        fn pair<T: std::fmt::Display>(pair: &Option<(T, T)>) -> String {
+            match pair {
+                Some((old, new)) => format!("[{},{}]", old, new),
+                None => "null".to_string(),
+            }
+        }
+        let cells: Vec<String> = self
+            .cells
+            .iter()
+            .map(|c| format!(r#"{{"addr":{},"old":{},"new":{}}}"#, c.addr, c.old, c.new))
+            .collect();
+        format!(
+            r#"{{"pc":{},"relative_base":{},"cells":[{}]}}"#,
+            pair(&self.pc),
+            pair(&self.relative_base),
+            cells.join(",")
+        )
+    }
+}
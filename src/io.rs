@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+#[test]
+fn test_io() {
+    use crate::computer::{Computer, Step};
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    // adds up its inputs until it reads a zero, printing the running sum
+    let program = "3,15,1006,15,14,1,15,16,16,4,16,1105,1,0,99,0,0";
+
+    let mut c = Computer::new(program).unwrap();
+    let mut io = IterIo::new(vec![1, 2, 3, 0].into_iter());
+    assert_eq!(c.resume_with(&mut io, &mut ()).unwrap(), Step::Halted);
+    assert_eq!(io.output, [1, 3, 6]);
+
+    let mut c = Computer::new(program).unwrap();
+    let mut sums = vec![];
+    let mut next = 5;
+    let mut io = Callbacks::new(
+        || {
+            next -= 1;
+            Some(next)
+        },
+        |value| sums.push(value),
+    );
+    assert_eq!(c.resume_with(&mut io, &mut ()).unwrap(), Step::Halted);
+    assert_eq!(sums, [4, 7, 9, 10]);
+
+    // runs on another thread, and blocks until its input arrives
+    let (to_computer, input) = channel();
+    let (output, from_computer) = channel();
+    let worker = thread::spawn(move || {
+        let mut c = Computer::new(program).unwrap();
+        c.resume_with(&mut Channels::new(input, output), &mut ())
+    });
+    for value in &[10, 20] {
+        to_computer.send(*value).unwrap();
+    }
+    assert_eq!(from_computer.recv(), Ok(10));
+    assert_eq!(from_computer.recv(), Ok(30));
+    to_computer.send(0).unwrap();
+    assert_eq!(worker.join().unwrap(), Ok(Step::Halted));
+}
+
+/// Where a `Computer` gets its input from, and where its output goes.
+///
+/// Like observers, this is passed to `Computer::step_with` and
+/// `Computer::resume_with` by generic type.
+pub trait Io<V = isize> {
+    /// The next input value. `None` makes the computer wait, so that it can
+    /// be resumed later.
+    fn input(&mut self) -> Option<V>;
+    fn output(&mut self, value: V);
+}
+
+/// Buffered input and output, which is what `Computer::run` uses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Queues<V = isize> {
+    pub input: VecDeque<V>,
+    pub output: VecDeque<V>,
+}
+
+impl<V> Io<V> for Queues<V> {
+    fn input(&mut self) -> Option<V> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: V) {
+        self.output.push_back(value);
+    }
+}
+
+/// Calls a closure for every input and output.
+pub struct Callbacks<I, O> {
+    input: I,
+    output: O,
+}
+
+impl<I, O> Callbacks<I, O> {
+    pub fn new(input: I, output: O) -> Callbacks<I, O> {
+        Callbacks { input, output }
+    }
+}
+
+impl<V, I: FnMut() -> Option<V>, O: FnMut(V)> Io<V> for Callbacks<I, O> {
+    fn input(&mut self) -> Option<V> {
+        (self.input)()
+    }
+
+    fn output(&mut self, value: V) {
+        (self.output)(value)
+    }
+}
+
+/// Takes the input from an iterator, and collects the output.
+pub struct IterIo<I: Iterator> {
+    input: I,
+    pub output: Vec<I::Item>,
+}
+
+impl<I: Iterator> IterIo<I> {
+    pub fn new(input: I) -> IterIo<I> {
+        IterIo {
+            input,
+            output: vec![],
+        }
+    }
+}
+
+impl<I: Iterator> Io<I::Item> for IterIo<I> {
+    fn input(&mut self) -> Option<I::Item> {
+        self.input.next()
+    }
+
+    fn output(&mut self, value: I::Item) {
+        self.output.push(value);
+    }
+}
+
+/// Connects a computer to channels, so it can run on its own thread.
+///
+/// Reading input blocks until a value arrives, and the computer only waits
+/// once the sending side has hung up. Output to a receiver that is gone is
+/// dropped.
+pub struct Channels<V = isize> {
+    input: Receiver<V>,
+    output: Sender<V>,
+}
+
+impl<V> Channels<V> {
+    pub fn new(input: Receiver<V>, output: Sender<V>) -> Channels<V> {
+        Channels { input, output }
+    }
+}
+
+impl<V> Io<V> for Channels<V> {
+    fn input(&mut self) -> Option<V> {
+        self.input.recv().ok()
+    }
+
+    fn output(&mut self, value: V) {
+        let _ = self.output.send(value);
+    }
+}
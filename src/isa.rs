@@ -0,0 +1,201 @@
+use crate::computer::ErrorKind;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_isa() {
+    use crate::computer::{Computer, Instruction, RunResult, Step};
+    use Access::{Read as R, Write as W};
+
+    fn div(operands: &[isize]) -> Result<Action, ErrorKind> {
+        match operands[0].checked_div(operands[1]) {
+            Some(quotient) => Ok(Action::Write(quotient)),
+            None => Err(ErrorKind::Extension("division by zero")),
+        }
+    }
+    fn modulo(operands: &[isize]) -> Result<Action, ErrorKind> {
+        Ok(Action::Write(
+            operands[0].checked_rem(operands[1]).unwrap_or(0),
+        ))
+    }
+    fn trap(_: &[isize]) -> Result<Action, ErrorKind> {
+        Ok(Action::Trap)
+    }
+    let mut set = InstructionSet::new();
+    set.register(10, Extension::new("DIV", &[R, R, W], div));
+    set.register(11, Extension::new("MOD", &[R, R, W], modulo));
+    set.register(12, Extension::new("TRAP", &[], trap));
+
+    // 17 / 5 and 17 % 5, with a trap in between
+    let program = vec![1110, 17, 5, 20, 12, 1111, 17, 5, 21, 4, 20, 4, 21, 99];
+    let mut computer: Computer = Computer::from_memory(program.clone());
+    computer.set_instruction_set(set.clone());
+    assert_eq!(computer.resume().unwrap(), Step::Trapped);
+    assert_eq!(computer.pc(), 5);
+    assert!(matches!(computer.run(), Ok(RunResult::Completed(2))));
+    assert_eq!(computer.output(), &[3, 2]);
+    assert_eq!(computer.cycles().get(12), 1);
+
+    let inst = Instruction::decode_with(&program, 0, &set).unwrap();
+    assert_eq!(inst.to_string(), "DIV  #17, #5, [20]");
+    assert_eq!(inst.encode(), &program[..4]);
+
+    // the built-in set does not know them
+    let mut computer: Computer = Computer::from_memory(program);
+    assert_eq!(
+        computer.run().err().unwrap().kind,
+        ErrorKind::InvalidOpcode(10)
+    );
+
+    let mut computer = Computer::new("1110,1,0,0,99").unwrap();
+    computer.set_instruction_set(set.clone());
+    assert_eq!(
+        computer.run().err().unwrap().to_string(),
+        "division by zero at pc 0 (instruction 1110)"
+    );
+
+    // writing needs a parameter to write to
+    set.register(10, Extension::new("BAD", &[R], |o| Ok(Action::Write(o[0]))));
+    let mut computer = Computer::new("1110,1,99").unwrap();
+    computer.set_instruction_set(set.clone());
+    assert_eq!(
+        computer.run().err().unwrap().kind,
+        ErrorKind::Extension("extension has no write parameter")
+    );
+
+    // built-in opcodes can be replaced and removed as well
+    set.register(
+        1,
+        Extension::new("SUB", &[R, R, W], |o| Ok(Action::Write(o[0] - o[1]))),
+    );
+    set.remove(2);
+    let mut computer = Computer::new("1101,7,2,0,4,0,1102,1,1,0").unwrap();
+    computer.set_instruction_set(set);
+    assert_eq!(computer.run().err().unwrap().pc, 6);
+    assert_eq!(computer.output(), &[5]);
+}
+
+/// How an extension instruction uses one of its parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The value is an operand of the semantics function.
+    Read,
+    /// The parameter is where `Action::Write` stores its value.
+    Write,
+}
+
+/// What an extension instruction does, once it has read its operands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action<V = isize> {
+    /// Goes on with the next instruction.
+    Continue,
+    /// Writes the value to the write parameter, and goes on.
+    Write(V),
+    Jump(V),
+    Halt,
+    /// Goes on with the next instruction, but stops running with
+    /// `Step::Trapped`, like a breakpoint compiled into the program.
+    Trap,
+}
+
+/// Gets the values of the read parameters, in order.
+pub type Semantics<V> = fn(&[V]) -> Result<Action<V>, ErrorKind<V>>;
+
+/// An opcode that is not built in, with its own parameters and semantics.
+#[derive(Debug, Clone)]
+pub struct Extension<V = isize> {
+    pub mnemonic: &'static str,
+    pub parameters: &'static [Access],
+    pub semantics: Semantics<V>,
+}
+
+impl<V> Extension<V> {
+    /// Panics if there are more parameters than the three of the longest
+    /// built-in instructions, or more than one of them is written.
+    pub fn new(
+        mnemonic: &'static str,
+        parameters: &'static [Access],
+        semantics: Semantics<V>,
+    ) -> Extension<V> {
+        assert!(
+            parameters.len() <= 3,
+            "{} has too many parameters",
+            mnemonic
+        );
+        assert!(
+            parameters.iter().filter(|a| **a == Access::Write).count() <= 1,
+            "{} writes more than one parameter",
+            mnemonic
+        );
+        Extension {
+            mnemonic,
+            parameters,
+            semantics,
+        }
+    }
+
+    /// The index of the parameter that is written, if any.
+    pub fn write_parameter(&self) -> Option<usize> {
+        self.parameters.iter().position(|a| *a == Access::Write)
+    }
+}
+
+/// The opcodes a `Computer` understands. The default is the built-in set
+/// of the puzzles, which extensions can be added to.
+#[derive(Debug, Clone)]
+pub struct InstructionSet<V = isize> {
+    /// The enabled built-in opcodes, one bit per opcode.
+    builtin: u128,
+    extensions: BTreeMap<isize, Extension<V>>,
+}
+
+/// Opcodes 1 to 9, and 99.
+const BUILTIN: u128 = 0b11_1111_1110 | 1 << 99;
+
+impl<V> Default for InstructionSet<V> {
+    fn default() -> InstructionSet<V> {
+        InstructionSet {
+            builtin: BUILTIN,
+            extensions: BTreeMap::new(),
+        }
+    }
+}
+
+impl<V> InstructionSet<V> {
+    pub fn new() -> InstructionSet<V> {
+        InstructionSet::default()
+    }
+
+    /// An instruction set without any opcodes, not even the built-in ones.
+    pub fn empty() -> InstructionSet<V> {
+        InstructionSet {
+            builtin: 0,
+            extensions: BTreeMap::new(),
+        }
+    }
+
+    /// Adds an extension, replacing whatever `opcode` did before.
+    ///
+    /// Panics if `opcode` is not between 0 and 99, as the digits above are
+    /// parameter modes.
+    pub fn register(&mut self, opcode: isize, extension: Extension<V>) {
+        assert!((0..100).contains(&opcode), "invalid opcode {}", opcode);
+        self.remove(opcode);
+        self.extensions.insert(opcode, extension);
+    }
+
+    /// Makes `opcode` invalid, whether it is built in or an extension.
+    pub fn remove(&mut self, opcode: isize) {
+        if (0..100).contains(&opcode) {
+            self.builtin &= !(1 << opcode);
+        }
+        self.extensions.remove(&opcode);
+    }
+
+    pub fn is_builtin(&self, opcode: isize) -> bool {
+        (0..100).contains(&opcode) && self.builtin & 1 << opcode != 0
+    }
+
+    pub fn extension(&self, opcode: isize) -> Option<&Extension<V>> {
+        self.extensions.get(&opcode)
+    }
+}
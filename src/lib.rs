@@ -0,0 +1,32 @@
+pub mod analysis;
+pub mod ascii;
+pub mod asm;
+pub mod bigint;
+pub mod cell;
+pub mod computer;
+pub mod coverage;
+pub mod debugger;
+pub mod disasm;
+pub mod fuzz;
+pub mod inspect;
+pub mod io;
+pub mod isa;
+pub mod memory;
+pub mod network;
+pub mod permute;
+pub mod pipeline;
+pub mod profile;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+pub mod transpile;
+
+pub mod day1;
+pub mod day2;
+pub mod day3;
+pub mod day4;
+pub mod day5;
+pub mod day6;
+pub mod day7;
+pub mod day8;
+pub mod day9;
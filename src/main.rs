@@ -1,14 +1,12 @@
-mod computer;
-
-mod day1;
-mod day2;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
-mod day7;
-mod day8;
-mod day9;
+use aoc2019::analysis;
+use aoc2019::ascii::Ascii;
+use aoc2019::computer::{format_memory, parse_memory, Computer};
+use aoc2019::coverage::Coverage;
+use aoc2019::debugger::Debugger;
+use aoc2019::profile::Profiler;
+use aoc2019::trace::Tracer;
+use aoc2019::{asm, disasm, fuzz, transpile};
+use aoc2019::{day1, day2, day3, day4, day5, day6, day7, day8, day9};
 
 pub fn process_level<F>(fun: F, level: usize, part: usize)
 where
@@ -20,7 +18,125 @@ where
     println!("level {}, part {}: {}", level, part, output);
 }
 
+fn read_program(path: &str) -> Vec<isize> {
+    let input = std::fs::read_to_string(path).expect("reading program");
+    parse_memory(input.trim()).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        std::process::exit(1);
+    })
+}
+
+fn load_computer(path: &str, inputs: &[&str]) -> Computer {
+    let mut computer = Computer::from_memory(read_program(path));
+    for input in inputs {
+        computer.push_input(input.parse().expect("parsing input"));
+    }
+    computer
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        [] => run_levels(),
+        ["disasm", path] => print!("{}", disasm::listing(&read_program(path))),
+        ["cfg", path] => print!("{}", analysis::analyze(&read_program(path)).to_dot()),
+        ["asm", path] => {
+            let source = std::fs::read_to_string(path).expect("reading source");
+            match asm::assemble(&source) {
+                Ok(memory) => println!("{}", format_memory(&memory)),
+                Err(err) => {
+                    eprintln!("{}: {}", path, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        ["debug", path, inputs @ ..] => {
+            let computer = load_computer(path, inputs);
+            let stdin = std::io::stdin();
+            Debugger::new(computer)
+                .repl(stdin.lock(), std::io::stdout())
+                .expect("running debugger");
+        }
+        ["trace", path, inputs @ ..] => {
+            let mut computer = load_computer(path, inputs);
+            let mut tracer = Tracer::new(std::io::BufWriter::new(std::io::stdout()));
+            let result = computer.run_observed(&mut tracer);
+            tracer.finish().expect("writing trace");
+            if let Err(err) = result {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        ["profile", path, inputs @ ..] => {
+            let mut computer = load_computer(path, inputs);
+            let mut profiler = Profiler::new();
+            if let Err(err) = computer.resume_observed(&mut profiler) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            let output: Vec<_> = computer.output().iter().map(|v| v.to_string()).collect();
+            println!("output: {}", output.join(" "));
+            print!("{}", profiler.report(computer.memory(), 20));
+        }
+        ["ascii", path] => {
+            let stdin = std::io::stdin();
+            let result =
+                Ascii::new(load_computer(path, &[])).interactive(stdin.lock(), std::io::stdout());
+            if let Err(err) = result {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        ["transpile", path] => {
+            let transpiled = transpile::transpile(&read_program(path));
+            for (pc, addr) in &transpiled.self_modifying {
+                eprintln!("warning: pc {} writes to code at {}", pc, addr);
+            }
+            if !transpiled.self_modifying.is_empty() {
+                eprintln!("warning: self-modifying program, the output only interprets");
+            }
+            print!("{}", transpiled.source);
+        }
+        ["coverage", path, runs @ ..] => {
+            // every argument is one run, with its inputs separated by commas
+            let program = read_program(path);
+            let mut coverage = Coverage::new();
+            for run in runs.iter().copied().chain(runs.is_empty().then_some("")) {
+                let inputs: Vec<&str> = run.split(',').filter(|s| !s.is_empty()).collect();
+                let mut computer = load_computer(path, &inputs);
+                let mut run_coverage = Coverage::new();
+                if let Err(err) = computer.resume_observed(&mut run_coverage) {
+                    eprintln!("{}: {}", run, err);
+                }
+                coverage.merge(&run_coverage);
+            }
+            print!("{}", coverage.listing(&program));
+            print!("{}", coverage.summary(&program));
+        }
+        ["fuzz", args @ ..] if args.len() <= 2 => {
+            let seed = args.first().map_or(1, |s| s.parse().expect("parsing seed"));
+            let runs = args
+                .get(1)
+                .map_or(10_000, |s| s.parse().expect("parsing runs"));
+            match fuzz::fuzz(seed, runs) {
+                Ok(()) => println!("{} programs agree", runs),
+                Err(counterexample) => {
+                    print!("{}", counterexample);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => {
+            eprintln!(
+                "usage: aoc2019 [disasm <program> | cfg <program> | asm <source> | debug <program> [inputs...] | trace <program> [inputs...] | profile <program> [inputs...] | ascii <program> | transpile <program> | fuzz [seed] [runs] | coverage <program> [inputs,...]...]"
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_levels() {
     process_level(day1::part1, 1, 1);
     process_level(day1::part2, 1, 2);
 
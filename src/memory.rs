@@ -0,0 +1,148 @@
+use crate::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+#[test]
+fn test_memory() {
+    use crate::computer::{Computer, ErrorKind};
+
+    // writes to a far-away address, and reads it back
+    let program = "1101,7,0,1000000000000,4,1000000000000,99";
+    let mut c = Computer::<isize, PagedMemory<isize>>::parse(program).unwrap();
+    assert_eq!(c.run_to_halt().unwrap(), 7);
+    assert_eq!(c.memory().allocated(), 2 * PAGE_SIZE);
+    assert_eq!(
+        c.memory().allocated_ranges(),
+        [
+            0..PAGE_SIZE,
+            976_562_500 * PAGE_SIZE..976_562_501 * PAGE_SIZE
+        ]
+    );
+
+    let mut c = Computer::new(program).unwrap();
+    c.set_memory_limit(Some(1 << 20));
+    let err = c.run().err().unwrap();
+    assert_eq!(err.pc, 0);
+    assert_eq!(err.kind, ErrorKind::MemoryLimit(1_000_000_000_000));
+
+    let mut c = Computer::<isize, PagedMemory<isize>>::parse(program).unwrap();
+    c.set_memory_limit(Some(PAGE_SIZE));
+    assert_eq!(
+        c.run().err().unwrap().kind,
+        ErrorKind::MemoryLimit(1_000_000_000_000)
+    );
+
+    // the default limit stops huge writes before they allocate
+    for (program, addr) in &[
+        ("1101,7,0,4611686018427387904,99", 1 << 62),
+        ("1101,7,0,10000000000,99", 10_000_000_000),
+    ] {
+        let mut c = Computer::new(program).unwrap();
+        assert_eq!(c.run().err().unwrap().kind, ErrorKind::MemoryLimit(*addr));
+    }
+}
+
+/// The storage behind the memory of a `Computer`.
+///
+/// Cells that were never written read as zero.
+pub trait Memory<V>: Clone + fmt::Debug {
+    fn from_vec(memory: Vec<V>) -> Self;
+    fn load(&self, addr: usize) -> V;
+    fn store(&mut self, addr: usize, value: V);
+    /// The number of cells that are currently allocated.
+    fn allocated(&self) -> usize;
+    /// The number of cells that would be allocated after storing to `addr`.
+    fn allocated_after_store(&self, addr: usize) -> usize;
+    /// The addresses of the allocated cells, in order.
+    fn allocated_ranges(&self) -> Vec<Range<usize>>;
+}
+
+/// Plain dense memory, which grows up to the highest written address.
+impl<V: Cell> Memory<V> for Vec<V> {
+    fn from_vec(memory: Vec<V>) -> Self {
+        memory
+    }
+
+    fn load(&self, addr: usize) -> V {
+        self.get(addr).cloned().unwrap_or_default()
+    }
+
+    fn store(&mut self, addr: usize, value: V) {
+        if addr >= self.len() {
+            self.resize(addr + 1, V::default());
+        }
+        self[addr] = value;
+    }
+
+    fn allocated(&self) -> usize {
+        self.len()
+    }
+
+    fn allocated_after_store(&self, addr: usize) -> usize {
+        self.len().max(addr + 1)
+    }
+
+    fn allocated_ranges(&self) -> Vec<Range<usize>> {
+        vec![Range {
+            start: 0,
+            end: self.len(),
+        }]
+    }
+}
+
+pub const PAGE_SIZE: usize = 1024;
+
+/// Sparse memory, which only allocates the pages that are written to.
+#[derive(Debug, Clone, Default)]
+pub struct PagedMemory<V> {
+    pages: HashMap<usize, Box<[V]>>,
+}
+
+impl<V: Cell> Memory<V> for PagedMemory<V> {
+    fn from_vec(memory: Vec<V>) -> Self {
+        let mut paged = PagedMemory {
+            pages: HashMap::new(),
+        };
+        for (addr, value) in memory.into_iter().enumerate() {
+            paged.store(addr, value);
+        }
+        paged
+    }
+
+    fn load(&self, addr: usize) -> V {
+        match self.pages.get(&(addr / PAGE_SIZE)) {
+            Some(page) => page[addr % PAGE_SIZE].clone(),
+            None => V::default(),
+        }
+    }
+
+    fn store(&mut self, addr: usize, value: V) {
+        let page = self
+            .pages
+            .entry(addr / PAGE_SIZE)
+            .or_insert_with(|| vec![V::default(); PAGE_SIZE].into_boxed_slice());
+        page[addr % PAGE_SIZE] = value;
+    }
+
+    fn allocated(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
+    }
+
+    fn allocated_after_store(&self, addr: usize) -> usize {
+        if self.pages.contains_key(&(addr / PAGE_SIZE)) {
+            self.allocated()
+        } else {
+            self.allocated() + PAGE_SIZE
+        }
+    }
+
+    fn allocated_ranges(&self) -> Vec<Range<usize>> {
+        let mut pages: Vec<usize> = self.pages.keys().copied().collect();
+        pages.sort_unstable();
+        pages
+            .into_iter()
+            .map(|page| page * PAGE_SIZE..(page + 1) * PAGE_SIZE)
+            .collect()
+    }
+}
@@ -0,0 +1,524 @@
+use crate::computer::{self, Computer, Step};
+use crate::io::Io;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_network() {
+    use crate::asm::assemble;
+
+    // forwards every packet to the next address with `y` incremented,
+    // node 0 starts off by sending the first one
+    let node = assemble(
+        "
+            IN [addr]
+            ADD [addr], #1, [next]
+            JNZ [addr], #loop
+            OUT #1
+            OUT #0
+            OUT #1
+        loop:
+            IN [x]
+            EQ [x], #-1, [idle]
+            JNZ [idle], #loop
+            IN [y]
+            ADD [y], #1, [y]
+            OUT [next]
+            OUT [x]
+            OUT [y]
+            JZ #0, #loop
+        addr: db 0
+        next: db 0
+        x:    db 0
+        y:    db 0
+        idle: db 0
+        ",
+    )
+    .unwrap();
+
+    // the packets leaving the last node go to the NAT, which sends the last
+    // one back to node 0 when the network is idle
+    let run = |threaded: bool| {
+        let mut network = Network::new(&Computer::from_memory(node.clone()), 3);
+        network.set_nat_address(3);
+        let mut received = vec![];
+        let mut monitor = |event: Event| match event {
+            Event::Nat(packet) => {
+                received.push(packet);
+                Reply::Continue
+            }
+            Event::Idle if received.len() >= 2 => Reply::Stop,
+            Event::Idle => Reply::Deliver(Packet {
+                dest: 0,
+                ..*received.last().unwrap()
+            }),
+        };
+        if threaded {
+            network.run_threaded(&mut monitor).unwrap();
+        } else {
+            network.run(&mut monitor).unwrap();
+        }
+        received.iter().map(|p| (p.dest, p.y)).collect::<Vec<_>>()
+    };
+    assert_eq!(run(false), [(3, 3), (3, 6)]);
+    assert_eq!(run(true), [(3, 3), (3, 6)]);
+
+    // node 0 halts right away, node 1 sends every packet on to the NAT,
+    // which keeps feeding it new ones whenever the network is idle
+    let halting = assemble(
+        "
+            IN [addr]
+            JZ [addr], #halt
+        loop:
+            IN [x]
+            EQ [x], #-1, [idle]
+            JNZ [idle], #loop
+            IN [y]
+            OUT #2
+            OUT [x]
+            OUT [y]
+            JZ #0, #loop
+        halt:
+            HLT
+        addr: db 0
+        x:    db 0
+        y:    db 0
+        idle: db 0
+        ",
+    )
+    .unwrap();
+    let run = |threaded: bool| {
+        let mut network = Network::new(&Computer::from_memory(halting.clone()), 2);
+        network.set_nat_address(2);
+        let mut received = vec![];
+        let mut monitor = |event: Event| match event {
+            Event::Nat(packet) => {
+                received.push(packet.y);
+                Reply::Continue
+            }
+            Event::Idle if received.len() >= 3 => Reply::Stop,
+            Event::Idle => Reply::Deliver(Packet {
+                dest: 1,
+                x: 0,
+                y: received.len() as isize,
+            }),
+        };
+        if threaded {
+            network.run_threaded(&mut monitor).unwrap();
+        } else {
+            network.run(&mut monitor).unwrap();
+        }
+        received
+    };
+    assert_eq!(run(false), [0, 1, 2]);
+    assert_eq!(run(true), [0, 1, 2]);
+
+    // a computer that neither reads nor writes still stops with the
+    // network, and keeps the budget it was given
+    let spinning = assemble(
+        "
+            OUT #1
+            OUT #2
+            OUT #3
+        spin:
+            JZ #0, #spin
+        ",
+    )
+    .unwrap();
+    let mut program = Computer::from_memory(spinning);
+    program.set_budget(Some(1 << 40));
+    for threaded in &[false, true] {
+        let mut network = Network::new(&program, 1);
+        network.set_nat_address(1);
+        let mut monitor = |_: Event| Reply::Stop;
+        if *threaded {
+            network.run_threaded(&mut monitor).unwrap();
+        } else {
+            network.run(&mut monitor).unwrap();
+        }
+        assert_eq!(network.computers()[0].budget(), Some(1 << 40));
+    }
+
+    let mut network = Network::new(&Computer::from_memory(node), 2);
+    let err = network.run(&mut |_: Event| Reply::Stop).unwrap_err();
+    assert_eq!(
+        err,
+        Error::UnknownAddress(Packet {
+            dest: 2,
+            x: 0,
+            y: 2
+        })
+    );
+}
+
+/// The address packets to the NAT are sent to, unless configured otherwise.
+pub const NAT_ADDRESS: isize = 255;
+
+/// How many instructions a computer runs per turn in `Network::run`, or
+/// before checking whether to stop in `Network::run_threaded`.
+const TIME_SLICE: u64 = 10_000;
+
+/// How many times in a row a computer needs to find its queue empty in
+/// `Network::run_threaded` before it counts as idle.
+const IDLE_READS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: isize,
+    pub x: isize,
+    pub y: isize,
+}
+
+/// What a `Monitor` gets notified about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A packet was sent to the NAT address.
+    Nat(Packet),
+    /// All the queues are empty, and every computer that has not halted is
+    /// waiting for input.
+    Idle,
+}
+
+/// How a `Monitor` wants the network to go on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    Continue,
+    /// Sends a packet to one of the computers, and continues.
+    Deliver(Packet),
+    Stop,
+}
+
+/// Watches the packets sent to the NAT address, and the network going idle.
+pub trait Monitor {
+    fn notify(&mut self, event: Event) -> Reply;
+}
+
+impl<F: FnMut(Event) -> Reply> Monitor for F {
+    fn notify(&mut self, event: Event) -> Reply {
+        self(event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The computer with the given address failed.
+    Computer(usize, computer::Error),
+    /// A packet was sent to an address that is neither a computer nor the NAT.
+    UnknownAddress(Packet),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Computer(addr, err) => write!(f, "computer {}: {}", addr, err),
+            Error::UnknownAddress(packet) => {
+                write!(f, "packet sent to unknown address {}", packet.dest)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A number of computers, which send each other packets of three values:
+/// the destination address, `x` and `y`.
+pub struct Network {
+    computers: Vec<Computer>,
+    nat_address: isize,
+}
+
+impl Network {
+    /// Creates `size` copies of `program`, each given its address as the
+    /// first input.
+    pub fn new(program: &Computer, size: usize) -> Network {
+        let computers = (0..size)
+            .map(|addr| {
+                let mut computer = program.clone();
+                computer.push_input(addr as isize);
+                computer
+            })
+            .collect();
+        Network {
+            computers,
+            nat_address: NAT_ADDRESS,
+        }
+    }
+
+    pub fn computers(&self) -> &[Computer] {
+        &self.computers
+    }
+
+    pub fn set_nat_address(&mut self, addr: isize) {
+        self.nat_address = addr;
+    }
+
+    /// Puts the packet into the input queue of its destination.
+    fn deliver(&mut self, packet: Packet) -> Result<(), Error> {
+        let computer = usize::try_from(packet.dest)
+            .ok()
+            .and_then(|addr| self.computers.get_mut(addr))
+            .ok_or(Error::UnknownAddress(packet))?;
+        computer.push_input(packet.x);
+        computer.push_input(packet.y);
+        Ok(())
+    }
+
+    /// Delivers the packet or hands it to the monitor. Returns whether the
+    /// monitor wants to stop.
+    fn route<M: Monitor>(&mut self, packet: Packet, monitor: &mut M) -> Result<bool, Error> {
+        if packet.dest != self.nat_address {
+            self.deliver(packet)?;
+            return Ok(false);
+        }
+        self.reply(monitor.notify(Event::Nat(packet)))
+    }
+
+    fn reply(&mut self, reply: Reply) -> Result<bool, Error> {
+        match reply {
+            Reply::Continue => Ok(false),
+            Reply::Deliver(packet) => self.deliver(packet).map(|_| false),
+            Reply::Stop => Ok(true),
+        }
+    }
+
+    /// Runs the computers in turns on the current thread, so the result is
+    /// the same every time. This returns when the monitor stops the network,
+    /// or when all computers have halted.
+    pub fn run<M: Monitor>(&mut self, monitor: &mut M) -> Result<(), Error> {
+        // halted computers are left out, whatever is sent to them
+        let mut halted = vec![false; self.computers.len()];
+        loop {
+            let mut idle = true;
+            for (addr, halted) in halted.iter_mut().enumerate() {
+                if *halted {
+                    continue;
+                }
+                let computer = &mut self.computers[addr];
+                if computer.input().is_empty() {
+                    computer.push_input(-1);
+                } else {
+                    idle = false;
+                }
+                let budget = computer.budget();
+                computer.set_budget(Some(TIME_SLICE));
+                let result = computer.resume();
+                computer.set_budget(budget);
+                match result {
+                    Ok(Step::Waiting) => {}
+                    Ok(Step::Halted) => *halted = true,
+                    Ok(_) => idle = false,
+                    Err(err) => return Err(Error::Computer(addr, err)),
+                }
+
+                while self.computers[addr].output().len() >= 3 {
+                    let output = self.computers[addr].output_mut();
+                    let mut next = || output.pop_front().unwrap();
+                    let packet = Packet {
+                        dest: next(),
+                        x: next(),
+                        y: next(),
+                    };
+                    idle = false;
+                    if self.route(packet, monitor)? {
+                        return Ok(());
+                    }
+                }
+            }
+            if halted.iter().all(|h| *h) {
+                return Ok(());
+            }
+            if idle && self.reply(monitor.notify(Event::Idle))? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs every computer on its own thread, while the monitor runs on the
+    /// current one. This returns when the monitor stops the network, or when
+    /// all computers have halted.
+    ///
+    /// Idle detection is a heuristic here: the network counts as idle once
+    /// all queues are empty, and every computer found its queue empty a few
+    /// times in a row without sending anything in between.
+    pub fn run_threaded<M: Monitor>(&mut self, monitor: &mut M) -> Result<(), Error> {
+        let shared = Mutex::new(Shared {
+            queues: self
+                .computers
+                .iter_mut()
+                .map(|c| std::mem::take(c.input_mut()))
+                .collect(),
+            idle_reads: vec![0; self.computers.len()],
+            finished: vec![false; self.computers.len()],
+            stop: false,
+            running: self.computers.len(),
+            error: None,
+        });
+        let (nat, packets) = mpsc::channel();
+        let nat_address = self.nat_address;
+
+        thread::scope(|scope| {
+            let shared = &shared;
+            for (addr, computer) in self.computers.iter_mut().enumerate() {
+                let mut io = NodeIo {
+                    addr,
+                    shared,
+                    nat: nat.clone(),
+                    nat_address,
+                    packet: vec![],
+                };
+                scope.spawn(move || {
+                    // in time slices, so that a computer that neither reads
+                    // nor writes still notices when the network stops
+                    let budget = computer.budget();
+                    let result = loop {
+                        computer.set_budget(Some(TIME_SLICE));
+                        match computer.resume_with(&mut io, &mut ()) {
+                            Ok(Step::OutOfBudget) if !shared.lock().unwrap().stop => {}
+                            result => break result,
+                        }
+                    };
+                    computer.set_budget(budget);
+                    computer.output_mut().extend(io.packet.drain(..));
+                    let mut shared = shared.lock().unwrap();
+                    shared.running -= 1;
+                    shared.finished[addr] = true;
+                    if let Err(err) = result {
+                        shared.fail(Error::Computer(addr, err));
+                    }
+                });
+            }
+            drop(nat);
+
+            loop {
+                let reply = match packets.recv_timeout(Duration::from_millis(1)) {
+                    Ok(packet) => monitor.notify(Event::Nat(packet)),
+                    Err(RecvTimeoutError::Timeout) => {
+                        let mut shared = shared.lock().unwrap();
+                        if shared.stop || shared.running == 0 {
+                            break;
+                        }
+                        if !shared.is_idle() {
+                            continue;
+                        }
+                        shared.idle_reads.iter_mut().for_each(|n| *n = 0);
+                        drop(shared);
+                        monitor.notify(Event::Idle)
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                let mut shared = shared.lock().unwrap();
+                match reply {
+                    Reply::Continue => {}
+                    Reply::Deliver(packet) => shared.deliver(packet),
+                    Reply::Stop => shared.stop = true,
+                }
+            }
+            shared.lock().unwrap().stop = true;
+        });
+
+        let shared = shared.into_inner().unwrap();
+        for (computer, queue) in self.computers.iter_mut().zip(shared.queues) {
+            *computer.input_mut() = queue;
+        }
+        match shared.error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The state the threads of `Network::run_threaded` share.
+struct Shared {
+    queues: Vec<VecDeque<isize>>,
+    /// How often each computer found its queue empty since it last read or
+    /// sent anything.
+    idle_reads: Vec<usize>,
+    /// Which computers have halted or failed.
+    finished: Vec<bool>,
+    stop: bool,
+    /// The number of computers that have not halted or failed yet.
+    running: usize,
+    error: Option<Error>,
+}
+
+impl Shared {
+    fn fail(&mut self, err: Error) {
+        self.error.get_or_insert(err);
+        self.stop = true;
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        match usize::try_from(packet.dest)
+            .ok()
+            .and_then(|addr| self.queues.get_mut(addr))
+        {
+            Some(queue) => queue.extend(&[packet.x, packet.y]),
+            None => self.fail(Error::UnknownAddress(packet)),
+        }
+    }
+
+    /// Whether every computer that is still running has nothing to read,
+    /// and found that out often enough.
+    fn is_idle(&self) -> bool {
+        (0..self.queues.len()).all(|addr| {
+            self.finished[addr]
+                || (self.queues[addr].is_empty() && self.idle_reads[addr] >= IDLE_READS)
+        })
+    }
+}
+
+/// The I/O of one computer in `Network::run_threaded`.
+struct NodeIo<'a> {
+    addr: usize,
+    shared: &'a Mutex<Shared>,
+    nat: Sender<Packet>,
+    nat_address: isize,
+    /// The values of the packet that is being sent.
+    packet: Vec<isize>,
+}
+
+impl Io for NodeIo<'_> {
+    fn input(&mut self) -> Option<isize> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.stop {
+            return None;
+        }
+        match shared.queues[self.addr].pop_front() {
+            Some(value) => {
+                shared.idle_reads[self.addr] = 0;
+                Some(value)
+            }
+            None => {
+                shared.idle_reads[self.addr] += 1;
+                drop(shared);
+                thread::yield_now();
+                Some(-1)
+            }
+        }
+    }
+
+    fn output(&mut self, value: isize) {
+        self.packet.push(value);
+        let mut shared = self.shared.lock().unwrap();
+        shared.idle_reads[self.addr] = 0;
+        if self.packet.len() < 3 {
+            return;
+        }
+        let packet = Packet {
+            dest: self.packet[0],
+            x: self.packet[1],
+            y: self.packet[2],
+        };
+        self.packet.clear();
+        if packet.dest == self.nat_address {
+            let _ = self.nat.send(packet);
+        } else {
+            shared.deliver(packet);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+#[test]
+fn test_permute() {
+    let mut all = vec![];
+    let mut p = Permutations::new(vec![1, 2, 3]);
+    while let Some(permutation) = p.next_permutation() {
+        all.push(permutation.to_vec());
+    }
+    assert_eq!(
+        all,
+        [
+            [1, 2, 3],
+            [1, 3, 2],
+            [2, 1, 3],
+            [2, 3, 1],
+            [3, 1, 2],
+            [3, 2, 1]
+        ]
+    );
+
+    let mut p = Permutations::starting_at(vec!['a', 'b', 'c'], 3);
+    assert_eq!(p.next_permutation(), Some(&['b', 'c', 'a'][..]));
+    assert!(Permutations::starting_at(vec![1, 2, 3], 6)
+        .next_permutation()
+        .is_none());
+
+    // more items than there are ranks in a `usize`
+    let items: Vec<usize> = (0..25).collect();
+    let mut p = Permutations::starting_at(items.clone(), 1);
+    assert_eq!(p.next_permutation().unwrap()[22..], [22, 24, 23]);
+    let mut p = Permutations::new(items.clone());
+    assert_eq!(p.next_permutation(), Some(&items[..]));
+
+    // weights the items by their position, so sorted descending wins
+    let items: Vec<isize> = (0..8).collect();
+    let score = |p: &[isize]| {
+        p.iter()
+            .enumerate()
+            .map(|(i, x)| (8 - i as isize) * x)
+            .sum::<isize>()
+    };
+    for threads in 1..4 {
+        assert_eq!(
+            par_max_by_key(&items, threads, score),
+            Some((168, vec![7, 6, 5, 4, 3, 2, 1, 0]))
+        );
+    }
+    // ties go to the first permutation
+    assert_eq!(
+        par_max_by_key(&items, 3, |_| 0),
+        Some((0, (0..8).collect()))
+    );
+}
+
+/// How many permutations a thread of `par_max_by_key` takes at a time.
+const CHUNK_SIZE: usize = 64;
+
+fn factorial(n: usize) -> Option<usize> {
+    (1..=n).try_fold(1usize, |acc, k| acc.checked_mul(k))
+}
+
+/// Goes through all the permutations of some items in lexicographic order
+/// of their original positions, without allocating.
+///
+/// This is not an `Iterator`, because each permutation it hands out is a
+/// view into its own buffer.
+pub struct Permutations<T> {
+    items: Vec<T>,
+    /// The original position of every item.
+    order: Vec<usize>,
+    started: bool,
+    done: bool,
+}
+
+impl<T> Permutations<T> {
+    pub fn new(items: Vec<T>) -> Permutations<T> {
+        Permutations::starting_at(items, 0)
+    }
+
+    /// Skips all permutations before the one with the given rank, so that
+    /// several threads can each take a part of them.
+    pub fn starting_at(mut items: Vec<T>, mut rank: usize) -> Permutations<T> {
+        let n = items.len();
+        let mut order: Vec<usize> = (0..n).collect();
+        let done = factorial(n).is_some_and(|count| rank >= count);
+        if !done {
+            // every digit of the rank in the factorial number system picks
+            // one of the remaining items, and a weight too large for a
+            // `usize` is larger than any rank
+            for i in 0..n {
+                let pick = match factorial(n - 1 - i) {
+                    Some(weight) => {
+                        let pick = rank / weight;
+                        rank %= weight;
+                        pick
+                    }
+                    None => 0,
+                };
+                order[i..=i + pick].rotate_right(1);
+                items[i..=i + pick].rotate_right(1);
+            }
+        }
+        Permutations {
+            items,
+            order,
+            started: false,
+            done,
+        }
+    }
+
+    pub fn next_permutation(&mut self) -> Option<&[T]> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            return Some(&self.items);
+        }
+        let order = &self.order;
+        let pivot = match (1..order.len()).rev().find(|&i| order[i - 1] < order[i]) {
+            Some(i) => i - 1,
+            None => {
+                self.done = true;
+                return None;
+            }
+        };
+        let swap = (pivot + 1..order.len())
+            .rev()
+            .find(|&j| order[pivot] < order[j])
+            .unwrap();
+        self.order.swap(pivot, swap);
+        self.items.swap(pivot, swap);
+        self.order[pivot + 1..].reverse();
+        self.items[pivot + 1..].reverse();
+        Some(&self.items)
+    }
+}
+
+/// Scores every permutation of `items` on `threads` threads, and returns
+/// the best score together with its permutation. Of several permutations
+/// with the best score, the first one wins, so the result does not depend
+/// on the number of threads.
+///
+/// Panics if there are too many items to count the permutations in a
+/// `usize`, which would take forever anyway.
+pub fn par_max_by_key<T, K, F>(items: &[T], threads: usize, score: F) -> Option<(K, Vec<T>)>
+where
+    T: Clone + Send + Sync,
+    K: Ord + Send,
+    F: Fn(&[T]) -> K + Sync,
+{
+    let count = factorial(items.len()).expect("too many permutations");
+    let next_chunk = AtomicUsize::new(0);
+    let best = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                // the best score on this thread, with its rank and permutation
+                let mut local: Option<(K, usize, Vec<T>)> = None;
+                loop {
+                    let start = next_chunk.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+                    if start >= count {
+                        break;
+                    }
+                    let mut permutations = Permutations::starting_at(items.to_vec(), start);
+                    for rank in start..count.min(start + CHUNK_SIZE) {
+                        let permutation = permutations.next_permutation().unwrap();
+                        let key = score(permutation);
+                        if local.as_ref().is_none_or(|(best, ..)| key > *best) {
+                            local = Some((key, rank, permutation.to_vec()));
+                        }
+                    }
+                }
+                let mut best = best.lock().unwrap();
+                if let Some(local) = local {
+                    let better = match &*best {
+                        Some((key, rank, _)) => {
+                            local.0 > *key || (local.0 == *key && local.1 < *rank)
+                        }
+                        None => true,
+                    };
+                    if better {
+                        *best = Some(local);
+                    }
+                }
+            });
+        }
+    });
+
+    best.into_inner()
+        .unwrap()
+        .map(|(key, _, permutation)| (key, permutation))
+}
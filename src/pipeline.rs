@@ -0,0 +1,153 @@
+use crate::computer::{self, Computer, Step};
+use std::fmt;
+
+#[test]
+fn test_pipeline() {
+    let node = |program: &str| Computer::new(program).unwrap();
+
+    // fan-out from a to b and c, which fan back in to d
+    let mut p = Pipeline::new();
+    let a = p.add_node(node("3,9,102,2,9,9,4,9,99,0"));
+    let b = p.add_node(node("3,9,101,1,9,9,4,9,99,0"));
+    let c = p.add_node(node("3,9,101,2,9,9,4,9,99,0"));
+    let d = p.add_node(node("3,11,3,12,1,11,12,11,4,11,99,0,0"));
+    p.connect(a, b);
+    p.connect(a, c);
+    p.chain(&[b, d]);
+    p.connect(c, d);
+    p.push_input(a, 5);
+    let report = p.run().unwrap();
+    assert_eq!(report.outputs, [vec![10], vec![11], vec![12], vec![23]]);
+    assert!(report.blocked.is_empty());
+
+    // a ring where nobody gets any input deadlocks right away
+    let mut p = Pipeline::new();
+    let nodes = [
+        p.add_node(node("3,9,102,2,9,9,4,9,99,0")),
+        p.add_node(node("3,9,102,2,9,9,4,9,99,0")),
+    ];
+    p.ring(&nodes);
+    let report = p.run().unwrap();
+    assert_eq!(report.blocked, nodes);
+
+    let mut p = Pipeline::new();
+    let failing = p.add_node(node("4,-1,99"));
+    let err = p.run().unwrap_err();
+    assert_eq!(err.node, failing);
+}
+
+/// Why `Pipeline::run` failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub node: usize,
+    pub error: computer::Error,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "node {}: {}", self.node, self.error)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// What happened during `Pipeline::run`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Everything each node has output, including the values that were
+    /// passed on to other nodes.
+    pub outputs: Vec<Vec<isize>>,
+    /// The nodes that had not halted when the pipeline stopped.
+    pub blocked: Vec<usize>,
+}
+
+impl Report {
+    pub fn last_output(&self, node: usize) -> Option<isize> {
+        self.outputs[node].last().copied()
+    }
+}
+
+/// A graph of computers, where the output of a node is passed on as input
+/// to all the nodes it is connected to. Nodes are numbered in the order
+/// they were added.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    nodes: Vec<Computer>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    pub fn add_node(&mut self, computer: Computer) -> usize {
+        self.nodes.push(computer);
+        self.edges.push(vec![]);
+        self.nodes.len() - 1
+    }
+
+    pub fn node(&self, node: usize) -> &Computer {
+        &self.nodes[node]
+    }
+
+    /// Passes the output of `from` on to `to`. A node connected to several
+    /// others sends every value to each of them.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.edges[from].push(to);
+    }
+
+    /// Connects each of the nodes to the next one.
+    pub fn chain(&mut self, nodes: &[usize]) {
+        for pair in nodes.windows(2) {
+            self.connect(pair[0], pair[1]);
+        }
+    }
+
+    /// Like `chain`, but also connects the last node back to the first.
+    pub fn ring(&mut self, nodes: &[usize]) {
+        self.chain(nodes);
+        if let (Some(first), Some(last)) = (nodes.first(), nodes.last()) {
+            self.connect(*last, *first);
+        }
+    }
+
+    /// Queues input for a node, like a phase setting.
+    pub fn push_input(&mut self, node: usize, value: isize) {
+        self.nodes[node].push_input(value);
+    }
+
+    /// Runs the nodes in turns, until all of them have halted or none of
+    /// them can go on because they are all waiting for input.
+    pub fn run(&mut self) -> Result<Report, Error> {
+        let mut outputs = vec![vec![]; self.nodes.len()];
+        let mut halted = vec![false; self.nodes.len()];
+        loop {
+            let mut progress = false;
+            for node in 0..self.nodes.len() {
+                if halted[node] {
+                    continue;
+                }
+                let computer = &mut self.nodes[node];
+                let cycles = computer.cycles().total();
+                let step = computer.resume().map_err(|error| Error { node, error })?;
+                halted[node] = step == Step::Halted;
+                progress |= computer.cycles().total() != cycles;
+
+                while let Some(value) = self.nodes[node].pop_output() {
+                    outputs[node].push(value);
+                    for &to in &self.edges[node] {
+                        self.nodes[to].push_input(value);
+                    }
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+        Ok(Report {
+            outputs,
+            blocked: (0..self.nodes.len()).filter(|n| !halted[*n]).collect(),
+        })
+    }
+}
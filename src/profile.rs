@@ -0,0 +1,142 @@
+use crate::cell::Cell;
+use crate::computer::{Effects, Instruction, Observer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+#[test]
+fn test_profile() {
+    use crate::computer::Computer;
+
+    // counts from 1 to 3
+    let program = "1101,0,1,18,4,18,1001,18,1,18,1007,18,4,19,1005,19,4,99,0,0";
+    let mut c = Computer::new(program).unwrap();
+    let mut profiler = Profiler::new();
+    c.run_observed(&mut profiler).unwrap();
+
+    assert_eq!(profiler.total(), 13);
+    assert_eq!(profiler.count(4), 3);
+    assert_eq!(profiler.opcode_count(1), 4);
+    assert_eq!(profiler.edge_count(14, 4), 2);
+    assert_eq!(profiler.edge_count(14, 17), 0);
+
+    let report = profiler.report(c.memory(), 2);
+    assert_eq!(
+        report,
+        "13 instructions executed
+
+ count      %   addr  instruction
+     3  23.1%      4: OUT  [18]
+     3  23.1%      6: ADD  [18], #1, [18]
+
+ count      %  opcode
+     4  30.8%  ADD
+     3  23.1%  OUT
+     3  23.1%  JNZ
+     3  23.1%  LT
+
+ count      %  jump
+     2  15.4%     14 -> 4
+"
+    );
+}
+
+/// An `Observer` that counts how often every address, opcode and taken
+/// jump is executed.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    by_pc: HashMap<usize, u64>,
+    by_opcode: BTreeMap<isize, (&'static str, u64)>,
+    /// Taken jumps, by the address of the jump and its target.
+    edges: HashMap<(usize, usize), u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.by_pc.values().sum()
+    }
+
+    /// How often the instruction at `pc` was executed.
+    pub fn count(&self, pc: usize) -> u64 {
+        self.by_pc.get(&pc).copied().unwrap_or(0)
+    }
+
+    pub fn opcode_count(&self, opcode: isize) -> u64 {
+        self.by_opcode.get(&opcode).map_or(0, |(_, count)| *count)
+    }
+
+    /// How often the jump at `from` went to `to`.
+    pub fn edge_count(&self, from: usize, to: usize) -> u64 {
+        self.edges.get(&(from, to)).copied().unwrap_or(0)
+    }
+
+    /// Lists the `top` hottest addresses with their disassembly, all
+    /// opcodes, and the `top` most taken jumps.
+    pub fn report(&self, memory: &[isize], top: usize) -> String {
+        let total = self.total();
+        let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        let mut out = format!("{} instructions executed\n", total);
+
+        // ties are broken by address, so the report is always the same
+        let mut hot: Vec<_> = self.by_pc.iter().map(|(pc, n)| (*pc, *n)).collect();
+        hot.sort_by_key(|(pc, count)| (std::cmp::Reverse(*count), *pc));
+        out.push_str("\n count      %   addr  instruction\n");
+        for (pc, count) in hot.into_iter().take(top) {
+            let inst = match Instruction::decode(memory, pc) {
+                Ok(inst) => inst.to_string(),
+                Err(_) => "??".to_string(),
+            };
+            writeln!(
+                out,
+                "{:>6} {:>5.1}%  {:>5}: {}",
+                count,
+                percent(count),
+                pc,
+                inst
+            )
+            .unwrap();
+        }
+
+        let mut opcodes: Vec<_> = self.by_opcode.values().collect();
+        opcodes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        out.push_str("\n count      %  opcode\n");
+        for (mnemonic, count) in opcodes {
+            writeln!(out, "{:>6} {:>5.1}%  {}", count, percent(*count), mnemonic).unwrap();
+        }
+
+        let mut edges: Vec<_> = self.edges.iter().map(|(e, n)| (*e, *n)).collect();
+        edges.sort_by_key(|(edge, count)| (std::cmp::Reverse(*count), *edge));
+        out.push_str("\n count      %  jump\n");
+        for ((from, to), count) in edges.into_iter().take(top) {
+            writeln!(
+                out,
+                "{:>6} {:>5.1}%  {:>5} -> {}",
+                count,
+                percent(count),
+                from,
+                to
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl<V: Cell> Observer<V> for Profiler {
+    fn after(&mut self, pc: usize, inst: &Instruction<V>, effects: &Effects<V>) {
+        *self.by_pc.entry(pc).or_insert(0) += 1;
+        self.by_opcode
+            .entry(inst.opcode())
+            .or_insert((inst.mnemonic(), 0))
+            .1 += 1;
+        if let Instruction::Jump(op, ..) = inst {
+            let (value, target) = (&effects.operands()[0], &effects.operands()[1]);
+            if let (true, Some(target)) = (op.taken(value), target.to_isize()) {
+                *self.edges.entry((pc, target as usize)).or_insert(0) += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::cell::Cell;
+use crate::computer::{format_memory, parse_memory, Computer};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+#[test]
+fn test_snapshot() {
+    use crate::computer::RunResult;
+
+    // adds up all its inputs, until it reads a zero
+    let program = "3,15,1006,15,12,1,15,16,16,1105,1,0,4,16,99,0,0";
+    let mut c = Computer::new(program).unwrap();
+    c.push_input(3);
+    c.push_input(4);
+    assert!(matches!(c.run(), Ok(RunResult::Waiting)));
+    c.push_input(5);
+
+    let mut file = vec![];
+    save(&c, &mut file).unwrap();
+    assert_eq!(
+        String::from_utf8(file.clone()).unwrap(),
+        "intcode-snapshot 1
+pc 0
+relative_base 0
+input 5
+output
+memory 3,15,1006,15,12,1,15,16,16,1105,1,0,4,16,99,4,7
+"
+    );
+
+    let mut restored = Computer::new("99").unwrap();
+    restore(&mut restored, file.as_slice()).unwrap();
+    restored.push_input(0);
+    assert_eq!(restored.run_to_halt().unwrap(), 12);
+
+    let err = load::<isize, _>("intcode-snapshot 2\n".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported snapshot version `2`");
+    let err = load::<isize, _>("intcode-snapshot 1\npc x\n".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 2: invalid `pc`");
+}
+
+/// The version written by `save`. Bump this when the format changes.
+const VERSION: &str = "1";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    UnsupportedVersion(String),
+    /// A missing or unparsable line, with its 1-based line number.
+    Invalid(usize, &'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported snapshot version `{}`", v),
+            Error::Invalid(line, key) => write!(f, "line {}: invalid `{}`", line, key),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+fn write_list<V: Cell, W: Write>(out: &mut W, key: &str, list: &[V]) -> io::Result<()> {
+    if list.is_empty() {
+        writeln!(out, "{}", key)
+    } else {
+        writeln!(out, "{} {}", key, format_memory(list))
+    }
+}
+
+/// Writes the complete state of `computer`: memory, pc, relative base and
+/// the pending input and output.
+///
+/// The format is plain text, with one `key value` pair per line, and lists
+/// of numbers in the same format as program text.
+pub fn save<V: Cell, W: Write>(computer: &Computer<V>, mut out: W) -> io::Result<()> {
+    writeln!(out, "intcode-snapshot {}", VERSION)?;
+    writeln!(out, "pc {}", computer.pc())?;
+    writeln!(out, "relative_base {}", computer.relative_base())?;
+    let input: Vec<_> = computer.input().iter().cloned().collect();
+    write_list(&mut out, "input", &input)?;
+    let output: Vec<_> = computer.output().iter().cloned().collect();
+    write_list(&mut out, "output", &output)?;
+    write_list(&mut out, "memory", computer.memory())?;
+    out.flush()
+}
+
+/// Reads a snapshot written by `save` into a fresh `Computer`.
+pub fn load<V: Cell, R: BufRead>(input: R) -> Result<Computer<V>, Error> {
+    let mut lines = input.lines();
+    let mut line_no = 0;
+    let mut next = |key: &'static str| -> Result<String, Error> {
+        line_no += 1;
+        let line = lines.next().ok_or(Error::Invalid(line_no, key))??;
+        match line.split_once(' ') {
+            Some((k, value)) if k == key => Ok(value.trim().to_string()),
+            None if line == key => Ok(String::new()),
+            _ => Err(Error::Invalid(line_no, key)),
+        }
+    };
+
+    let version = next("intcode-snapshot")?;
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    let pc = next("pc")?.parse().map_err(|_| Error::Invalid(2, "pc"))?;
+    let relative_base = next("relative_base")?
+        .parse()
+        .map_err(|_| Error::Invalid(3, "relative_base"))?;
+    let parse_list = |value: String, line: usize, key| {
+        if value.is_empty() {
+            return Ok(vec![]);
+        }
+        parse_memory(&value).map_err(|_| Error::Invalid(line, key))
+    };
+    let input = parse_list(next("input")?, 4, "input")?;
+    let output = parse_list(next("output")?, 5, "output")?;
+    let memory = parse_list(next("memory")?, 6, "memory")?;
+
+    let mut computer = Computer::from_memory(memory);
+    computer.set_pc(pc);
+    computer.set_relative_base(relative_base);
+    computer.input_mut().extend(input);
+    computer.output_mut().extend(output);
+    Ok(computer)
+}
+
+/// Replaces the state of a running `computer` with a snapshot.
+pub fn restore<V: Cell, R: BufRead>(computer: &mut Computer<V>, input: R) -> Result<(), Error> {
+    let loaded = load(input)?;
+    computer.set_memory(loaded.memory().to_vec());
+    computer.set_pc(loaded.pc());
+    computer.set_relative_base(loaded.relative_base());
+    *computer.input_mut() = loaded.input().clone();
+    *computer.output_mut() = loaded.output().clone();
+    Ok(())
+}
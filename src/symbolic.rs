@@ -0,0 +1,510 @@
+use crate::computer::JumpOp;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[test]
+fn test_symbolic() {
+    // [0] = [1] * [13] + [2], after a first instruction that reads from the
+    // symbolic addresses in [1] and [2], but whose result is overwritten
+    let program = [1, 0, 0, 3, 2, 1, 13, 3, 1, 3, 2, 0, 99, 100];
+    let mut s = Symbolic::new(&program);
+    let noun = s.symbolize(1, 0..=99);
+    let verb = s.symbolize(2, 0..=99);
+    s.run().unwrap();
+    assert_eq!(s.peek(3).to_string(), "100*s0");
+    assert_eq!(s.peek(0).to_string(), "100*s0 + s1");
+    let solution = solve(s.peek(0), 1234, s.domains()).unwrap();
+    assert_eq!((solution[noun], solution[verb]), (12, 34));
+    assert_eq!(solve(s.peek(0), 10_000, s.domains()), None);
+
+    // outputs can be solved for just the same
+    let mut s = Symbolic::new(&[3, 0, 1002, 0, 3, 0, 4, 0, 99]);
+    s.push_symbolic_input(-10..=10);
+    s.run().unwrap();
+    assert_eq!(solve(&s.output()[0], -21, s.domains()), Some(vec![-7]));
+    assert_eq!(solve(&s.output()[0], 22, s.domains()), None);
+
+    // -s0 = isize::MIN has no solution that fits
+    let mut s = Symbolic::new(&[1002, 5, -1, 0, 99, 0]);
+    s.symbolize(5, isize::MIN..=isize::MAX);
+    s.run().unwrap();
+    assert_eq!(solve(s.peek(0), isize::MIN, s.domains()), None);
+
+    let mut s = Symbolic::new(&[1106, 1, -1, 104, 5, 99]);
+    s.run().unwrap();
+    assert_eq!(s.output(), [Value::constant(5)]);
+
+    let mut s = Symbolic::new(&[3, 0, 1005, 0, 0, 99]);
+    s.push_symbolic_input(0..=1);
+    assert_eq!(s.run(), Err(Error::SymbolicBranch(2)));
+}
+
+/// How many instructions `Symbolic::run` executes before giving up.
+const STEP_LIMIT: usize = 1_000_000;
+
+/// A polynomial over the symbols, with a coefficient for every product of
+/// symbols. The empty product is the constant term.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Poly {
+    terms: BTreeMap<Vec<usize>, isize>,
+}
+
+impl Poly {
+    pub fn constant(value: isize) -> Poly {
+        let mut poly = Poly::default();
+        if value != 0 {
+            poly.terms.insert(vec![], value);
+        }
+        poly
+    }
+
+    pub fn symbol(symbol: usize) -> Poly {
+        let mut poly = Poly::default();
+        poly.terms.insert(vec![symbol], 1);
+        poly
+    }
+
+    /// The value, if it does not depend on any symbol.
+    pub fn as_constant(&self) -> Option<isize> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((product, coefficient)) if product.is_empty() && self.terms.len() == 1 => {
+                Some(*coefficient)
+            }
+            _ => None,
+        }
+    }
+
+    fn add_term(&mut self, product: Vec<usize>, coefficient: isize) -> Option<()> {
+        let sum = self
+            .terms
+            .get(&product)
+            .unwrap_or(&0)
+            .checked_add(coefficient)?;
+        if sum == 0 {
+            self.terms.remove(&product);
+        } else {
+            self.terms.insert(product, sum);
+        }
+        Some(())
+    }
+
+    /// The sum, or `None` if a coefficient overflows.
+    pub fn checked_add(&self, other: &Poly) -> Option<Poly> {
+        let mut sum = self.clone();
+        for (product, coefficient) in &other.terms {
+            sum.add_term(product.clone(), *coefficient)?;
+        }
+        Some(sum)
+    }
+
+    /// The product, or `None` if a coefficient overflows.
+    pub fn checked_mul(&self, other: &Poly) -> Option<Poly> {
+        let mut result = Poly::default();
+        for (a, x) in &self.terms {
+            for (b, y) in &other.terms {
+                let mut product: Vec<usize> = a.iter().chain(b).copied().collect();
+                product.sort_unstable();
+                result.add_term(product, x.checked_mul(*y)?)?;
+            }
+        }
+        Some(result)
+    }
+
+    /// The value for the given symbol values, or `None` on overflow.
+    pub fn eval(&self, values: &[isize]) -> Option<isize> {
+        self.terms
+            .iter()
+            .try_fold(0isize, |sum, (product, coefficient)| {
+                let term = product
+                    .iter()
+                    .try_fold(*coefficient, |acc, s| acc.checked_mul(values[*s]))?;
+                sum.checked_add(term)
+            })
+    }
+
+    /// The coefficient of `symbol`, if that is the only term it appears in.
+    fn linear_coefficient(&self, symbol: usize) -> Option<isize> {
+        let mut found = None;
+        for (product, coefficient) in &self.terms {
+            if product.contains(&symbol) {
+                if product.len() > 1 || found.is_some() {
+                    return None;
+                }
+                found = Some(*coefficient);
+            }
+        }
+        found
+    }
+
+    fn symbols(&self) -> Vec<usize> {
+        let mut symbols: Vec<usize> = self.terms.keys().flatten().copied().collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
+}
+
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // the constant term sorts first, but reads best last
+        let mut terms: Vec<_> = self.terms.iter().collect();
+        let constant = terms[0].0.is_empty();
+        terms.rotate_left(usize::from(constant));
+        for (i, (product, coefficient)) in terms.into_iter().enumerate() {
+            let sign = if *coefficient < 0 { "-" } else { "+" };
+            match i {
+                0 if *coefficient < 0 => write!(f, "-")?,
+                0 => {}
+                _ => write!(f, " {} ", sign)?,
+            }
+            let magnitude = coefficient.unsigned_abs();
+            let symbols: Vec<_> = product.iter().map(|s| format!("s{}", s)).collect();
+            match (magnitude, symbols.is_empty()) {
+                (_, true) => write!(f, "{}", magnitude)?,
+                (1, false) => write!(f, "{}", symbols.join("*"))?,
+                (_, false) => write!(f, "{}*{}", magnitude, symbols.join("*"))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The contents of a memory cell, an input or an output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Known(Poly),
+    /// Depends on the symbols in a way that is not tracked, like the result
+    /// of a comparison, or a load from a symbolic address.
+    Opaque,
+}
+
+impl Value {
+    fn constant(value: isize) -> Value {
+        Value::Known(Poly::constant(value))
+    }
+
+    pub fn as_constant(&self) -> Option<isize> {
+        match self {
+            Value::Known(poly) => poly.as_constant(),
+            Value::Opaque => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Known(poly) => write!(f, "{}", poly),
+            Value::Opaque => write!(f, "?"),
+        }
+    }
+}
+
+/// Why symbolic execution stopped. Every variant but `StepLimit` has the
+/// pc of the instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The instruction does not decode, or its opcode depends on a symbol.
+    InvalidInstruction(usize),
+    /// A jump condition depends on a symbol.
+    SymbolicBranch(usize),
+    /// A write, jump target or relative base change depends on a symbol.
+    SymbolicAddress(usize),
+    NegativeAddress(usize),
+    Overflow(usize),
+    MissingInput(usize),
+    StepLimit,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidInstruction(pc) => write!(f, "invalid instruction at pc {}", pc),
+            Error::SymbolicBranch(pc) => write!(f, "symbolic branch at pc {}", pc),
+            Error::SymbolicAddress(pc) => write!(f, "symbolic address at pc {}", pc),
+            Error::NegativeAddress(pc) => write!(f, "negative address at pc {}", pc),
+            Error::Overflow(pc) => write!(f, "arithmetic overflow at pc {}", pc),
+            Error::MissingInput(pc) => write!(f, "missing input at pc {}", pc),
+            Error::StepLimit => write!(f, "step limit reached"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Runs a program with some memory cells and inputs replaced by symbols,
+/// keeping track of the polynomial every value is of them.
+///
+/// Control flow has to stay concrete, so this is meant for straight-line
+/// programs like the ones of day 2.
+#[derive(Debug, Clone)]
+pub struct Symbolic {
+    memory: Vec<Value>,
+    pc: usize,
+    relative_base: isize,
+    input: VecDeque<Value>,
+    output: Vec<Value>,
+    /// The values each symbol can take.
+    domains: Vec<RangeInclusive<isize>>,
+}
+
+impl Symbolic {
+    pub fn new(memory: &[isize]) -> Symbolic {
+        Symbolic {
+            memory: memory.iter().map(|v| Value::constant(*v)).collect(),
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: vec![],
+            domains: vec![],
+        }
+    }
+
+    fn new_symbol(&mut self, domain: RangeInclusive<isize>) -> usize {
+        self.domains.push(domain);
+        self.domains.len() - 1
+    }
+
+    /// Replaces a memory cell with a new symbol, and returns the symbol.
+    pub fn symbolize(&mut self, addr: usize, domain: RangeInclusive<isize>) -> usize {
+        let symbol = self.new_symbol(domain);
+        self.store(addr, Value::Known(Poly::symbol(symbol)));
+        symbol
+    }
+
+    pub fn push_input(&mut self, value: isize) {
+        self.input.push_back(Value::constant(value));
+    }
+
+    /// Queues a new symbol as input, and returns the symbol.
+    pub fn push_symbolic_input(&mut self, domain: RangeInclusive<isize>) -> usize {
+        let symbol = self.new_symbol(domain);
+        self.input.push_back(Value::Known(Poly::symbol(symbol)));
+        symbol
+    }
+
+    pub fn domains(&self) -> &[RangeInclusive<isize>] {
+        &self.domains
+    }
+
+    pub fn peek(&self, addr: usize) -> &Value {
+        static ZERO: Value = Value::Known(Poly {
+            terms: BTreeMap::new(),
+        });
+        self.memory.get(addr).unwrap_or(&ZERO)
+    }
+
+    pub fn output(&self) -> &[Value] {
+        &self.output
+    }
+
+    fn store(&mut self, addr: usize, value: Value) {
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, Value::constant(0));
+        }
+        self.memory[addr] = value;
+    }
+
+    /// Turns a value into an address, if it is known.
+    fn address(&self, value: &Value) -> Result<Option<usize>, Error> {
+        match value.as_constant() {
+            Some(addr) if addr < 0 => Err(Error::NegativeAddress(self.pc)),
+            Some(addr) => Ok(Some(addr as usize)),
+            None => Ok(None),
+        }
+    }
+
+    /// The address a parameter in `mode` with the raw `word` refers to, or
+    /// `None` if that depends on a symbol.
+    fn param_address(&self, mode: isize, word: &Value) -> Result<Option<usize>, Error> {
+        match mode {
+            0 => self.address(word),
+            _ => match word {
+                Value::Known(offset) => {
+                    let addr = Poly::constant(self.relative_base)
+                        .checked_add(offset)
+                        .ok_or(Error::Overflow(self.pc))?;
+                    self.address(&Value::Known(addr))
+                }
+                Value::Opaque => Ok(None),
+            },
+        }
+    }
+
+    fn read(&self, mode: isize, word: &Value) -> Result<Value, Error> {
+        if mode == 1 {
+            return Ok(word.clone());
+        }
+        Ok(match self.param_address(mode, word)? {
+            Some(addr) => self.peek(addr).clone(),
+            None => Value::Opaque,
+        })
+    }
+
+    fn write(&mut self, mode: isize, word: &Value, value: Value) -> Result<(), Error> {
+        if mode == 1 {
+            return Err(Error::InvalidInstruction(self.pc));
+        }
+        match self.param_address(mode, word)? {
+            Some(addr) => {
+                self.store(addr, value);
+                Ok(())
+            }
+            None => Err(Error::SymbolicAddress(self.pc)),
+        }
+    }
+
+    /// Executes one instruction, and returns whether the program halted.
+    fn step(&mut self) -> Result<bool, Error> {
+        let pc = self.pc;
+        let word = self
+            .peek(pc)
+            .as_constant()
+            .ok_or(Error::InvalidInstruction(pc))?;
+        let (opcode, modes) = (word % 100, word / 100);
+        let count = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            3 | 4 | 9 => 1,
+            5 | 6 => 2,
+            99 => return Ok(true),
+            _ => return Err(Error::InvalidInstruction(pc)),
+        };
+        let mut params = vec![];
+        for n in 0..count {
+            let mode = modes / 10isize.pow(n as u32) % 10;
+            if !(0..=2).contains(&mode) {
+                return Err(Error::InvalidInstruction(pc));
+            }
+            params.push((mode, self.peek(pc + 1 + n).clone()));
+        }
+        let read = |n: usize| self.read(params[n].0, &params[n].1);
+
+        let mut next = pc + count + 1;
+        match opcode {
+            1 | 2 | 7 | 8 => {
+                let value = match (read(0)?, read(1)?) {
+                    (Value::Known(a), Value::Known(b)) => match opcode {
+                        1 => Value::Known(a.checked_add(&b).ok_or(Error::Overflow(pc))?),
+                        2 => Value::Known(a.checked_mul(&b).ok_or(Error::Overflow(pc))?),
+                        _ => match (a.as_constant(), b.as_constant()) {
+                            (Some(a), Some(b)) if opcode == 7 => Value::constant((a < b) as isize),
+                            (Some(a), Some(b)) => Value::constant((a == b) as isize),
+                            _ => Value::Opaque,
+                        },
+                    },
+                    _ => Value::Opaque,
+                };
+                self.write(params[2].0, &params[2].1, value)?;
+            }
+            3 => {
+                let value = self.input.pop_front().ok_or(Error::MissingInput(pc))?;
+                self.write(params[0].0, &params[0].1, value)?;
+            }
+            4 => {
+                let value = read(0)?;
+                self.output.push(value);
+            }
+            5 | 6 => {
+                let value = read(0)?.as_constant().ok_or(Error::SymbolicBranch(pc))?;
+                let target = read(1)?;
+                let op = if opcode == 5 {
+                    JumpOp::IfNotZero
+                } else {
+                    JumpOp::IfZero
+                };
+                if op.taken(&value) {
+                    next = self.address(&target)?.ok_or(Error::SymbolicAddress(pc))?;
+                }
+            }
+            _ => {
+                let value = read(0)?.as_constant().ok_or(Error::SymbolicAddress(pc))?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(value)
+                    .ok_or(Error::Overflow(pc))?;
+            }
+        }
+        self.pc = next;
+        Ok(false)
+    }
+
+    /// Runs until the program halts.
+    pub fn run(&mut self) -> Result<(), Error> {
+        for _ in 0..STEP_LIMIT {
+            if self.step()? {
+                return Ok(());
+            }
+        }
+        Err(Error::StepLimit)
+    }
+}
+
+/// Finds values for all symbols within their domains that make `value`
+/// equal `target`.
+///
+/// Symbols that `value` does not depend on are set to the start of their
+/// domains. Of the symbols that only appear in a single linear term, the
+/// last one is solved for directly, while all others are enumerated. Of
+/// several solutions, this finds the first one in lexicographic order of
+/// the enumerated symbols.
+pub fn solve(
+    value: &Value,
+    target: isize,
+    domains: &[RangeInclusive<isize>],
+) -> Option<Vec<isize>> {
+    let poly = match value {
+        Value::Known(poly) => poly,
+        Value::Opaque => return None,
+    };
+    let mut values: Vec<isize> = domains.iter().map(|d| *d.start()).collect();
+    let mut enumerated = poly.symbols();
+    let solved = enumerated
+        .iter()
+        .rposition(|s| poly.linear_coefficient(*s).is_some())
+        .map(|i| enumerated.remove(i));
+    if enumerated.iter().any(|s| domains[*s].is_empty()) {
+        return None;
+    }
+
+    loop {
+        match solved {
+            Some(s) => {
+                // target = coefficient * s + rest, where rest is the value
+                // with s = 0
+                values[s] = 0;
+                let rest = poly.eval(&values);
+                let coefficient = poly.linear_coefficient(s).unwrap();
+                if let Some(diff) = rest.and_then(|rest| target.checked_sub(rest)) {
+                    let value = diff
+                        .checked_div(coefficient)
+                        .filter(|_| diff.checked_rem(coefficient) == Some(0));
+                    if let Some(value) = value.filter(|v| domains[s].contains(v)) {
+                        values[s] = value;
+                        return Some(values);
+                    }
+                }
+            }
+            None if poly.eval(&values) == Some(target) => return Some(values),
+            None => {}
+        }
+
+        // the next combination of the enumerated symbols, like an odometer
+        let mut carry = true;
+        for s in enumerated.iter().rev() {
+            if values[*s] < *domains[*s].end() {
+                values[*s] += 1;
+                carry = false;
+                break;
+            }
+            values[*s] = *domains[*s].start();
+        }
+        if carry {
+            return None;
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::cell::Cell;
+use crate::computer::{Effects, Instruction, Observer};
+use std::io::{self, Write};
+
+#[test]
+fn test_trace() {
+    use crate::computer::Computer;
+
+    let mut c = Computer::new_with_input("3,9,1001,9,5,9,4,9,99,0", 3).unwrap();
+    let mut tracer = Tracer::new(vec![]);
+    c.run_observed(&mut tracer).unwrap();
+    let trace = String::from_utf8(tracer.finish().unwrap()).unwrap();
+    assert_eq!(
+        trace,
+        "    0: IN   [9]             | [9]=3
+    2: ADD  [9], #5, [9]    | 3 5 | [9]=8
+    6: OUT  [9]             | 8
+"
+    );
+
+    // observers that do not look at the effects get them empty
+    struct Counter(usize);
+    impl Observer for Counter {
+        const WANTS_EFFECTS: bool = false;
+        fn after(&mut self, _: usize, _: &Instruction, effects: &Effects) {
+            assert!(effects.operands().is_empty() && effects.write.is_none());
+            self.0 += 1;
+        }
+    }
+    let mut c = Computer::new_with_input("3,9,1001,9,5,9,4,9,99,0", 3).unwrap();
+    let mut counter = Counter(0);
+    c.run_observed(&mut counter).unwrap();
+    assert_eq!(counter.0, 3);
+    assert_eq!(c.output(), &[8]);
+}
+
+/// An `Observer` that writes one line per executed instruction, with the
+/// operand values it read and the memory it wrote.
+pub struct Tracer<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W) -> Tracer<W> {
+        Tracer { out, error: None }
+    }
+
+    /// Returns the writer, or the first error that happened while tracing.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => self.out.flush().map(|_| self.out),
+        }
+    }
+
+    fn write_line<V: Cell>(
+        &mut self,
+        pc: usize,
+        inst: &Instruction<V>,
+        effects: &Effects<V>,
+    ) -> io::Result<()> {
+        let line = format!("{:>5}: {:<20} |", pc, inst.to_string());
+        write!(self.out, "{}", line)?;
+        if !effects.operands().is_empty() {
+            for value in effects.operands() {
+                write!(self.out, " {}", value)?;
+            }
+            if effects.write.is_some() {
+                write!(self.out, " |")?;
+            }
+        }
+        if let Some((addr, value)) = &effects.write {
+            write!(self.out, " [{}]={}", addr, value)?;
+        }
+        writeln!(self.out)
+    }
+}
+
+impl<V: Cell, W: Write> Observer<V> for Tracer<W> {
+    fn after(&mut self, pc: usize, inst: &Instruction<V>, effects: &Effects<V>) {
+        if self.error.is_none() {
+            self.error = self.write_line(pc, inst, effects).err();
+        }
+    }
+}
@@ -0,0 +1,528 @@
+use crate::analysis::{self, Analysis};
+use crate::computer::{ArithmeticOp, Instruction, JumpOp, Parameter, RelationOp};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[test]
+fn test_transpile() {
+    use crate::asm::assemble;
+    use crate::computer::{Computer, RunResult};
+
+    // the return address pushed to the stack becomes an entry point
+    let program = assemble(
+        "
+            IN [x]
+            ARB #100
+            ADD #0, #back, rb+0
+            JZ #0, #double
+        back:
+            OUT [x]
+            HLT
+        double:
+            MUL [x], #2, [x]
+            JZ #0, rb+0
+        x:  db 0
+        ",
+    )
+    .unwrap();
+    let transpiled = transpile(&program);
+    assert!(transpiled.self_modifying.is_empty());
+    assert_eq!(transpiled.entries, [0, 11]);
+    let source = &transpiled.source;
+    assert!(source.contains("const PROGRAM: &[isize] = &[\n    3, 21, 109, 100,"));
+    assert!(source.contains("const CODE: &[(usize, usize)] = &[(0, 21)];\n"));
+    assert!(source.contains("            11 => {\n                // 11: OUT  [21]\n"));
+    assert!(source
+        .contains("                let v = a.checked_mul(b).ok_or((14, Fault::Overflow))?;\n"));
+    assert!(
+        source.contains("                let t: isize = m.rd(m.rel(0).map_err(|f| (18, f))?);\n")
+    );
+    assert!(source.contains("                    pc = addr(t).map_err(|f| (18, f))?;\n"));
+    assert!(source.contains("fn interpret("));
+
+    // statically known self-modification leaves everything to the
+    // interpreter
+    let transpiled = transpile(&[1101, 1, 2, 5, 104, 0, 99]);
+    assert_eq!(transpiled.self_modifying, [(0, 5)]);
+    assert!(!transpiled.source.contains("            0 => {"));
+
+    // the compiled programs behave like `Computer`, also when an
+    // instruction runs past the end of the program
+    let dir = std::env::temp_dir().join(format!("transpile-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let programs: &[(&[isize], &[isize])] = &[
+        (&program, &[21]),
+        (&[1101, 1, 2, 5, 104, 0, 99], &[]),
+        (&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], &[8]),
+        (&[3, 0, 4, 0, 99], &[]),
+        (&[1106, 1, -1, 104, 5, 99], &[]),
+        (&[4], &[]),
+        (&[104, 7, 6, 5], &[]),
+    ];
+    for (i, (program, input)) in programs.iter().enumerate() {
+        let mut computer: Computer = Computer::from_memory(program.to_vec());
+        input.iter().for_each(|v| computer.push_input(*v));
+        let result = computer.run();
+        let mut expected = String::new();
+        for value in computer.output() {
+            writeln!(expected, "{}", value).unwrap();
+        }
+        let (status, message) = match result {
+            Ok(RunResult::Completed(_)) => (0, String::new()),
+            Ok(_) => (2, "waiting for input\n".to_string()),
+            Err(err) => (1, format!("{}\n", err)),
+        };
+
+        let source = dir.join(format!("{}.rs", i));
+        let binary = dir.join(i.to_string());
+        std::fs::write(&source, transpile(program).source).unwrap();
+        let compiled = std::process::Command::new("rustc")
+            .args(["--edition", "2018", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .output()
+            .unwrap();
+        assert!(compiled.status.success(), "{:?}", compiled);
+        let ran = std::process::Command::new(&binary)
+            .args(input.iter().map(|v| v.to_string()))
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&ran.stdout),
+            expected,
+            "{:?}",
+            program
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&ran.stderr),
+            message,
+            "{:?}",
+            program
+        );
+        assert_eq!(ran.status.code(), Some(status), "{:?}", program);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The result of `transpile`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transpiled {
+    /// A standalone Rust program.
+    pub source: String,
+    /// The addresses the compiled code can be entered at.
+    pub entries: Vec<usize>,
+    /// The self-modifying writes found by the analysis, as `(pc, address)`.
+    /// If there are any, nothing is compiled, and the generated program
+    /// only interprets.
+    pub self_modifying: Vec<(usize, usize)>,
+}
+
+/// The constants that arithmetic instructions write relative to the base,
+/// which is how programs put return addresses on the stack.
+fn return_addresses(analysis: &Analysis) -> BTreeSet<usize> {
+    let mut constants = BTreeSet::new();
+    for block in &analysis.blocks {
+        for (_, inst) in &block.instructions {
+            if let Instruction::Arithmetic(
+                op,
+                Parameter::Immediate(a),
+                Parameter::Immediate(b),
+                Parameter::Relative(_),
+            ) = inst
+            {
+                let value = match op {
+                    ArithmeticOp::Add => a.checked_add(*b),
+                    ArithmeticOp::Multiply => a.checked_mul(*b),
+                };
+                if let Some(value) = value.filter(|v| *v >= 0) {
+                    constants.insert(value as usize);
+                }
+            }
+        }
+    }
+    constants
+}
+
+/// An expression for the value of a parameter of the instruction at `pc`.
+fn read(param: &Parameter, pc: usize) -> String {
+    match param {
+        Parameter::Immediate(value) => format!("{}isize", value),
+        Parameter::Position(addr) => format!("m.rd({})", addr),
+        Parameter::Relative(offset) => format!("m.rd(m.rel({}).map_err(|f| ({}, f))?)", offset, pc),
+    }
+}
+
+/// An expression for the address a parameter of the instruction at `pc`
+/// writes to.
+fn address(param: &Parameter, pc: usize) -> String {
+    match param {
+        Parameter::Position(addr) => addr.to_string(),
+        Parameter::Relative(offset) => format!("m.rel({}).map_err(|f| ({}, f))?", offset, pc),
+        Parameter::Immediate(_) => format!("return Err(({}, Fault::WriteToImmediate))", pc),
+    }
+}
+
+/// The statements for one instruction, which end the block if they leave
+/// the compiled code.
+fn compile(out: &mut String, pc: usize, inst: &Instruction) {
+    let indent = "                ";
+    let next = pc + inst.width();
+    let mut line = |s: String| writeln!(out, "{}{}", indent, s).unwrap();
+    line(format!("// {}: {}", pc, inst));
+    let store = |dst: &Parameter| {
+        format!(
+            "let d = {};\n{i}if m.wr(d, v) {{\n{i}    return Ok(Exit::Fallback({}));\n{i}}}",
+            address(dst, pc),
+            next,
+            i = indent
+        )
+    };
+    match inst {
+        Instruction::Arithmetic(op, lhs, rhs, dst) => {
+            let op = match op {
+                ArithmeticOp::Add => "checked_add",
+                ArithmeticOp::Multiply => "checked_mul",
+            };
+            line(format!("let a: isize = {};", read(lhs, pc)));
+            line(format!("let b: isize = {};", read(rhs, pc)));
+            line(format!(
+                "let v = a.{}(b).ok_or(({}, Fault::Overflow))?;",
+                op, pc
+            ));
+            line(store(dst));
+        }
+        Instruction::Relation(op, lhs, rhs, dst) => {
+            let op = match op {
+                RelationOp::LessThan => "<",
+                RelationOp::Equal => "==",
+            };
+            line(format!("let a: isize = {};", read(lhs, pc)));
+            line(format!("let b: isize = {};", read(rhs, pc)));
+            line(format!("let v = (a {} b) as isize;", op));
+            line(store(dst));
+        }
+        Instruction::Input(dst) => {
+            line("let v = match m.input.pop_front() {".to_string());
+            line("    Some(v) => v,".to_string());
+            line("    None => return Ok(Exit::Wait),".to_string());
+            line("};".to_string());
+            line(store(dst));
+        }
+        Instruction::Output(value) => {
+            line(format!("m.output.push({});", read(value, pc)));
+        }
+        Instruction::Jump(op, value, target) => {
+            let op = match op {
+                JumpOp::IfNotZero => "!=",
+                JumpOp::IfZero => "==",
+            };
+            line(format!("let c: isize = {};", read(value, pc)));
+            line(format!("let t: isize = {};", read(target, pc)));
+            line(format!("if c {} 0 {{", op));
+            line(format!("    pc = addr(t).map_err(|f| ({}, f))?;", pc));
+            line("    continue;".to_string());
+            line("}".to_string());
+        }
+        Instruction::AdjustBase(value) => {
+            line(format!(
+                "m.rb = m.rb.checked_add({}).ok_or(({}, Fault::Overflow))?;",
+                read(value, pc),
+                pc
+            ));
+        }
+        Instruction::Halt => line(format!("return Ok(Exit::Halt({}));", pc)),
+        // only built-in opcodes are analyzed, so this is left to the interpreter
+        Instruction::Extension(..) => line(format!("return Ok(Exit::Fallback({}));", pc)),
+    }
+}
+
+/// Translates a program into a standalone Rust program, which takes its
+/// inputs as command line arguments, prints all outputs, and fails like
+/// `Computer::run` does.
+///
+/// All code that is reachable from address 0, or from a constant that is
+/// pushed to the stack like a return address, is compiled into a state machine over its
+/// basic blocks. Jumps anywhere else, and writes into the compiled code,
+/// hand over to an interpreter that is part of the generated program.
+pub fn transpile(memory: &[isize]) -> Transpiled {
+    let mut entries = vec![0];
+    let analysis = loop {
+        let analysis = analysis::analyze_from(memory, &entries);
+        let more: Vec<usize> = return_addresses(&analysis)
+            .into_iter()
+            .filter(|c| *c < memory.len() && !entries.contains(c))
+            .collect();
+        if more.is_empty() {
+            break analysis;
+        }
+        entries.extend(more);
+    };
+    entries.sort_unstable();
+    let self_modifying = analysis.self_modifying.clone();
+    let blocks = if self_modifying.is_empty() {
+        &analysis.blocks[..]
+    } else {
+        &[]
+    };
+
+    let mut out = String::from("// Generated by `aoc2019 transpile`.\n\n");
+    out.push_str("const PROGRAM: &[isize] = &[\n");
+    for chunk in memory.chunks(12) {
+        let words: Vec<_> = chunk.iter().map(|w| w.to_string()).collect();
+        writeln!(out, "    {},", words.join(", ")).unwrap();
+    }
+    out.push_str("];\n\n");
+
+    // the compiled instructions, as half-open ranges of addresses
+    let mut code: Vec<(usize, usize)> = vec![];
+    let words: BTreeSet<usize> = blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .flat_map(|(pc, inst)| *pc..pc + inst.width())
+        .collect();
+    for addr in words {
+        match code.last_mut() {
+            Some(range) if range.1 == addr => range.1 += 1,
+            _ => code.push((addr, addr + 1)),
+        }
+    }
+    let ranges: Vec<_> = code
+        .iter()
+        .map(|(a, b)| format!("({}, {})", a, b))
+        .collect();
+    writeln!(
+        out,
+        "const CODE: &[(usize, usize)] = &[{}];\n",
+        ranges.join(", ")
+    )
+    .unwrap();
+
+    out.push_str("#[allow(unused_mut, unused_variables)]\n");
+    out.push_str("fn compiled(m: &mut Machine, mut pc: usize) -> Result<Exit, (usize, Fault)> {\n");
+    out.push_str("    loop {\n        match pc {\n");
+    for block in blocks {
+        writeln!(out, "            {} => {{", block.start).unwrap();
+        for (pc, inst) in &block.instructions {
+            compile(&mut out, *pc, inst);
+        }
+        if !matches!(block.instructions.last(), Some((_, Instruction::Halt))) {
+            writeln!(out, "                pc = {};", block.end()).unwrap();
+        }
+        out.push_str("            }\n");
+    }
+    out.push_str("            _ => return Ok(Exit::Fallback(pc)),\n        }\n    }\n}\n");
+    out.push_str(RUNTIME);
+
+    Transpiled {
+        source: out,
+        entries,
+        self_modifying,
+    }
+}
+
+/// The part of every generated program that does not depend on the
+/// Intcode program.
+const RUNTIME: &str = r#"
+enum Exit {
+    Halt(usize),
+    Wait,
+    /// Execution has to go on in the interpreter at this pc.
+    Fallback(usize),
+}
+
+enum Fault {
+    InvalidOpcode(isize),
+    InvalidParameterMode(isize),
+    WriteToImmediate,
+    NegativeAddress(isize),
+    Overflow,
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Fault::InvalidOpcode(code) => write!(f, "invalid opcode {}", code),
+            Fault::InvalidParameterMode(mode) => write!(f, "unknown parameter mode {}", mode),
+            Fault::WriteToImmediate => write!(f, "writing to an Immediate parameter"),
+            Fault::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            Fault::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+struct Machine {
+    mem: Vec<isize>,
+    /// Whether each address belongs to a compiled instruction.
+    code: Vec<bool>,
+    rb: isize,
+    input: std::collections::VecDeque<isize>,
+    output: Vec<isize>,
+}
+
+fn addr(value: isize) -> Result<usize, Fault> {
+    if value < 0 {
+        return Err(Fault::NegativeAddress(value));
+    }
+    Ok(value as usize)
+}
+
+impl Machine {
+    fn rd(&self, addr: usize) -> isize {
+        self.mem.get(addr).copied().unwrap_or(0)
+    }
+
+    /// Returns whether the write changed compiled code.
+    fn wr(&mut self, addr: usize, value: isize) -> bool {
+        if addr >= self.mem.len() {
+            self.mem.resize(addr + 1, 0);
+        }
+        self.mem[addr] = value;
+        self.code.get(addr).copied().unwrap_or(false)
+    }
+
+    fn rel(&self, offset: isize) -> Result<usize, Fault> {
+        addr(self.rb.checked_add(offset).ok_or(Fault::Overflow)?)
+    }
+
+    fn param(&self, pc: usize, modes: isize, n: usize) -> Result<Param, Fault> {
+        let value = self.rd(pc + n);
+        match modes / 10isize.pow(n as u32 - 1) % 10 {
+            0 => Ok(Param::Pos(addr(value)?)),
+            1 => Ok(Param::Imm(value)),
+            2 => Ok(Param::Rel(value)),
+            mode => Err(Fault::InvalidParameterMode(mode)),
+        }
+    }
+
+    fn get(&self, param: &Param) -> Result<isize, Fault> {
+        match *param {
+            Param::Pos(addr) => Ok(self.rd(addr)),
+            Param::Imm(value) => Ok(value),
+            Param::Rel(offset) => Ok(self.rd(self.rel(offset)?)),
+        }
+    }
+
+    fn set(&mut self, param: &Param, value: isize) -> Result<(), Fault> {
+        let addr = match *param {
+            Param::Pos(addr) => addr,
+            Param::Imm(_) => return Err(Fault::WriteToImmediate),
+            Param::Rel(offset) => self.rel(offset)?,
+        };
+        self.wr(addr, value);
+        Ok(())
+    }
+}
+
+enum Param {
+    Pos(usize),
+    Imm(isize),
+    Rel(isize),
+}
+
+/// Executes the instruction at `pc`, and returns where to go on.
+fn step(m: &mut Machine, pc: usize) -> Result<Result<usize, Exit>, Fault> {
+    let word = m.rd(pc);
+    let (code, modes) = (word % 100, word / 100);
+    let width = match code {
+        1 | 2 | 7 | 8 => 4,
+        3 | 4 | 9 => 2,
+        5 | 6 => 3,
+        99 => 1,
+        _ => return Err(Fault::InvalidOpcode(code)),
+    };
+    let p = (1..width)
+        .map(|n| m.param(pc, modes, n))
+        .collect::<Result<Vec<_>, _>>()?;
+    match code {
+        1 | 2 | 7 | 8 => {
+            let (a, b) = (m.get(&p[0])?, m.get(&p[1])?);
+            let v = match code {
+                1 => a.checked_add(b).ok_or(Fault::Overflow)?,
+                2 => a.checked_mul(b).ok_or(Fault::Overflow)?,
+                7 => (a < b) as isize,
+                _ => (a == b) as isize,
+            };
+            m.set(&p[2], v)?;
+        }
+        3 => match m.input.pop_front() {
+            Some(v) => m.set(&p[0], v)?,
+            None => return Ok(Err(Exit::Wait)),
+        },
+        4 => {
+            let v = m.get(&p[0])?;
+            m.output.push(v);
+        }
+        5 | 6 => {
+            let c = m.get(&p[0])?;
+            let t = m.get(&p[1])?;
+            if (c != 0) == (code == 5) {
+                return Ok(Ok(addr(t)?));
+            }
+        }
+        9 => {
+            let v = m.get(&p[0])?;
+            m.rb = m.rb.checked_add(v).ok_or(Fault::Overflow)?;
+        }
+        _ => return Ok(Err(Exit::Halt(pc))),
+    }
+    Ok(Ok(pc + width))
+}
+
+fn interpret(m: &mut Machine, mut pc: usize) -> Result<Exit, (usize, Fault)> {
+    loop {
+        pc = match step(m, pc) {
+            Ok(Ok(next)) => next,
+            Ok(Err(exit)) => return Ok(exit),
+            Err(fault) => return Err((pc, fault)),
+        };
+    }
+}
+
+fn fail(m: &Machine, pc: usize, message: &dyn std::fmt::Display) -> ! {
+    eprintln!("{} at pc {} (instruction {})", message, pc, m.rd(pc));
+    std::process::exit(1);
+}
+
+fn main() {
+    let input = std::env::args()
+        .skip(1)
+        .map(|arg| {
+            arg.parse().unwrap_or_else(|_| {
+                eprintln!("invalid input `{}`", arg);
+                std::process::exit(1);
+            })
+        })
+        .collect();
+    // the last instruction may read parameters past the end of the program
+    let len = CODE.iter().map(|r| r.1).fold(PROGRAM.len(), usize::max);
+    let mut code = vec![false; len];
+    for &(start, end) in CODE {
+        code[start..end].iter_mut().for_each(|c| *c = true);
+    }
+    let mut m = Machine {
+        mem: PROGRAM.to_vec(),
+        code,
+        rb: 0,
+        input,
+        output: vec![],
+    };
+
+    // once the interpreter has taken over, the compiled code may be stale
+    let result = match compiled(&mut m, 0) {
+        Ok(Exit::Fallback(pc)) => interpret(&mut m, pc),
+        result => result,
+    };
+    for value in &m.output {
+        println!("{}", value);
+    }
+    match result {
+        Ok(Exit::Halt(pc)) if m.output.is_empty() => fail(&m, pc, &"halting without output"),
+        Ok(Exit::Halt(_)) => {}
+        Ok(_) => {
+            eprintln!("waiting for input");
+            std::process::exit(2);
+        }
+        Err((pc, fault)) => fail(&m, pc, &fault),
+    }
+}
+"#;